@@ -0,0 +1,19 @@
+use bevy::prelude::{Component, Resource};
+
+#[derive(Component)]
+pub struct ChatText;
+
+/**
+ * Everything said since joining the room, oldest first.
+ */
+#[derive(Resource, Default)]
+pub struct ChatHistory(pub Vec<(String, String)>);
+
+/**
+ * The message being typed, other controls are ignored while typing.
+ */
+#[derive(Resource, Default)]
+pub struct ChatInput {
+    pub typing: bool,
+    pub text: String,
+}
@@ -0,0 +1,41 @@
+use self::{
+    components::{ChatHistory, ChatInput, ChatText},
+    systems::{clear_history, receive_chat, setup, teardown, type_chat, update_chat_text},
+};
+use crate::GameState;
+use bevy::prelude::*;
+use bevy_matchbox::{prelude::MultipleChannels, MatchboxSocket};
+pub mod components;
+mod systems;
+
+/**
+ * Lets everyone in the room talk to each other, in the lobby and during the match.
+ */
+pub struct ChatPlugin;
+impl Plugin for ChatPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ChatHistory>()
+            .init_resource::<ChatInput>()
+            .add_system(clear_history.in_schedule(OnEnter(GameState::Lobby)))
+            .add_system(setup.in_schedule(OnEnter(GameState::Lobby)))
+            .add_system(
+                setup
+                    .run_if(resource_exists::<MatchboxSocket<MultipleChannels>>())
+                    .in_schedule(OnEnter(GameState::InGame)),
+            )
+            .add_systems(
+                (
+                    receive_chat
+                        .run_if(resource_exists::<MatchboxSocket<MultipleChannels>>())
+                        .run_if(any_with_component::<ChatText>()),
+                    type_chat
+                        .run_if(resource_exists::<MatchboxSocket<MultipleChannels>>())
+                        .run_if(any_with_component::<ChatText>()),
+                    update_chat_text.run_if(any_with_component::<ChatText>()),
+                )
+                    .chain(),
+            )
+            .add_system(teardown.in_schedule(OnExit(GameState::Lobby)))
+            .add_system(teardown.in_schedule(OnExit(GameState::InGame)));
+    }
+}
@@ -0,0 +1,163 @@
+use bevy::prelude::*;
+use bevy_matchbox::{prelude::MultipleChannels, MatchboxSocket};
+
+use crate::{
+    config::PlayerName,
+    game::{
+        pause::PauseMenu,
+        protocol::{ChatMessage, CHAT_CHANNEL},
+    },
+    FontAssets,
+};
+
+use super::components::{ChatHistory, ChatInput, ChatText};
+
+/**
+ * How many of the latest messages the chat box shows.
+ */
+pub const CHAT_LINES: usize = 6;
+/**
+ * Older messages are forgotten once there are more than this.
+ */
+pub const CHAT_HISTORY: usize = 50;
+/**
+ * Longest message that can be typed, in characters.
+ */
+pub const MAX_MESSAGE_LENGTH: usize = 100;
+
+pub fn clear_history(mut history: ResMut<ChatHistory>) {
+    history.0.clear();
+}
+
+pub fn setup(mut commands: Commands, fonts: Res<FontAssets>) {
+    commands.spawn((
+        TextBundle::from_sections([TextSection::new(
+            "",
+            TextStyle {
+                font: fonts.visitor.clone(),
+                font_size: 16.0,
+                color: Color::WHITE,
+            },
+        )])
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                bottom: Val::Px(8.0),
+                left: Val::Px(8.0),
+                ..default()
+            },
+            max_size: Size::width(Val::Percent(60.0)),
+            ..default()
+        }),
+        ChatText,
+    ));
+}
+
+fn remember(history: &mut ChatHistory, name: String, text: String) {
+    history.0.push((name, text));
+    let forgotten = history.0.len().saturating_sub(CHAT_HISTORY);
+    history.0.drain(..forgotten);
+}
+
+pub fn receive_chat(
+    mut socket: ResMut<MatchboxSocket<MultipleChannels>>,
+    mut history: ResMut<ChatHistory>,
+) {
+    for (peer, packet) in socket.channel(CHAT_CHANNEL).receive() {
+        match ChatMessage::from_packet(&packet) {
+            Some(message) => remember(&mut history, message.name, message.text),
+            None => warn!("Received an unknown chat message from {:?}", peer),
+        }
+    }
+}
+
+/**
+ * Enter starts typing and sends the message, sending nothing just closes the chat box.
+ */
+pub fn type_chat(
+    keys: Res<Input<KeyCode>>,
+    mut characters: EventReader<ReceivedCharacter>,
+    mut socket: ResMut<MatchboxSocket<MultipleChannels>>,
+    name: Res<PlayerName>,
+    pause: Res<PauseMenu>,
+    mut input: ResMut<ChatInput>,
+    mut history: ResMut<ChatHistory>,
+) {
+    // characters typed while playing are read anyway, so they don't end up in the next message
+    let typed: String = characters
+        .iter()
+        .map(|event| event.char)
+        .filter(|char| !char.is_control())
+        .collect();
+
+    if !input.typing {
+        // enter picks menu items while the pause menu is open
+        if keys.just_pressed(KeyCode::Return) && !pause.open {
+            input.typing = true;
+        }
+        return;
+    }
+
+    if keys.just_pressed(KeyCode::Return) {
+        input.typing = false;
+        let text = std::mem::take(&mut input.text).trim().to_string();
+        if text.is_empty() {
+            return;
+        }
+
+        let message = ChatMessage {
+            name: name.0.clone(),
+            text,
+        };
+        let peers: Vec<_> = socket.connected_peers().collect();
+        for peer in peers {
+            socket.channel(CHAT_CHANNEL).send(message.to_packet(), peer);
+        }
+        remember(&mut history, message.name, message.text);
+        return;
+    }
+
+    if keys.just_pressed(KeyCode::Back) {
+        input.text.pop();
+    }
+    let room = MAX_MESSAGE_LENGTH.saturating_sub(input.text.chars().count());
+    input.text.extend(typed.chars().take(room));
+}
+
+pub fn update_chat_text(
+    history: Res<ChatHistory>,
+    input: Res<ChatInput>,
+    mut texts: Query<&mut Text, With<ChatText>>,
+) {
+    if !history.is_changed() && !input.is_changed() {
+        return;
+    }
+
+    let skipped = history.0.len().saturating_sub(CHAT_LINES);
+    let mut lines: Vec<_> = history
+        .0
+        .iter()
+        .skip(skipped)
+        .map(|(name, text)| format!("{name}: {text}"))
+        .collect();
+    lines.push(if input.typing {
+        format!("> {}_", input.text)
+    } else {
+        "Enter to chat".into()
+    });
+
+    for mut text in texts.iter_mut() {
+        text.sections[0].value = lines.join("\n");
+    }
+}
+
+pub fn teardown(
+    mut commands: Commands,
+    mut input: ResMut<ChatInput>,
+    texts: Query<(Entity, With<ChatText>)>,
+) {
+    for (entity, _) in texts.iter() {
+        commands.entity(entity).despawn();
+    }
+    *input = ChatInput::default();
+}
@@ -1,10 +1,156 @@
+use std::net::IpAddr;
+
 use bevy::prelude::Resource;
 
+use crate::game::classes::CharacterClass;
+
+pub const MIN_PLAYERS: usize = 2;
+pub const MAX_PLAYERS: usize = 4;
+/**
+ * GGRS can't roll back further than its max prediction window of 8 frames.
+ */
+pub const MAX_CHECK_DISTANCE: usize = 7;
+/**
+ * Port of the signaling server when hosting or joining a host.
+ */
+pub const DEFAULT_SIGNALING_PORT: u16 = 3536;
+
 #[derive(Resource)]
 pub struct Debug(pub bool);
+
+/**
+ * The signaling server of a player hosting, the port can be left out when it's the default one.
+ */
+pub fn host_server(address: &str) -> String {
+    if address.contains(':') {
+        format!("ws://{address}")
+    } else {
+        format!("ws://{address}:{DEFAULT_SIGNALING_PORT}")
+    }
+}
+
+/**
+ * The signaling server running in this process, if we're hosting one.
+ */
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct SignalingHost {
+    pub port: u16,
+    /// Where the others on the LAN reach us, if we could tell.
+    pub address: Option<IpAddr>,
+}
+
+impl SignalingHost {
+    /**
+     * What the others type in to join, the port is left out when it's the default one.
+     */
+    pub fn join_address(&self) -> Option<String> {
+        let address = self.address?;
+        Some(if self.port == DEFAULT_SIGNALING_PORT {
+            address.to_string()
+        } else {
+            format!("{address}:{}", self.port)
+        })
+    }
+}
+
+/**
+ * The name other players see us by.
+ */
+#[derive(Resource, Debug, Clone)]
+pub struct PlayerName(pub String);
+
+impl Default for PlayerName {
+    fn default() -> Self {
+        PlayerName("Player".into())
+    }
+}
+
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum GameMode {
+    /// Matched up with other players through the signaling server.
+    #[default]
+    Online,
+    /// A local session with a single player, no network needed.
+    Solo,
+    /// A local session rolling back every frame, warning when the resimulated frames differ.
+    SyncTest,
+    /// A local session playing back the inputs of a recorded match.
+    Replay,
+}
+
+/**
+ * The replay file to play back.
+ */
+#[derive(Resource, Debug, Clone)]
+pub struct ReplayPath(pub String);
+
+/**
+ * How many frames a sync test rolls back and resimulates every frame.
+ */
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct CheckDistance(pub usize);
+
+impl Default for CheckDistance {
+    fn default() -> Self {
+        CheckDistance(2)
+    }
+}
+
+/**
+ * Where to find other players and how many of them to wait for.
+ */
+#[derive(Resource, Debug, Clone)]
+pub struct MatchConfig {
+    pub server: String,
+    pub room: String,
+    pub players: usize,
+    /// Peers in the room only watching the match, on top of the players.
+    pub spectators: usize,
+    /// Whether we're one of the spectators.
+    pub spectate: bool,
+}
+
+impl Default for MatchConfig {
+    fn default() -> Self {
+        MatchConfig {
+            server: "ws://127.0.0.1:3536".into(),
+            room: "dungeons_of_redrod".into(),
+            players: 2,
+            spectators: 0,
+            spectate: false,
+        }
+    }
+}
+
+impl MatchConfig {
+    /**
+     * The matchbox room url, peers are matched in groups of players and spectators.
+     */
+    pub fn room_url(&self) -> String {
+        format!(
+            "{}/{}?next={}",
+            self.server.trim_end_matches('/'),
+            self.room,
+            self.peers()
+        )
+    }
+
+    pub fn peers(&self) -> usize {
+        self.players + self.spectators
+    }
+}
+
 #[derive(Default)]
 pub struct ProgramConfig {
     pub debug: bool,
+    pub name: PlayerName,
+    pub class: CharacterClass,
+    pub mode: GameMode,
+    pub check_distance: CheckDistance,
+    pub matchmaking: MatchConfig,
+    pub replay: Option<ReplayPath>,
+    /// Port to run a signaling server on for the other players to join.
+    pub host: Option<u16>,
 }
 
 impl ProgramConfig {
@@ -14,15 +160,177 @@ impl ProgramConfig {
             return Ok(cfg);
         }
 
-        for arg in args {
+        let mut host = false;
+        let mut join = None;
+        let mut port = None;
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
             match arg.as_ref() {
                 "-d" | "--debug" => {
                     cfg.debug = true;
                 }
+                "--solo" => {
+                    cfg.mode = GameMode::Solo;
+                }
+                "--synctest" => {
+                    cfg.mode = GameMode::SyncTest;
+                }
+                "--replay" => {
+                    let path = args.next().ok_or("missing replay file")?;
+                    cfg.mode = GameMode::Replay;
+                    cfg.replay = Some(ReplayPath(path.clone()));
+                }
+                "--check-distance" => {
+                    let frames = args.next().ok_or("missing check distance")?;
+                    let frames = frames.parse().map_err(|_| "invalid check distance")?;
+                    if !(1..=MAX_CHECK_DISTANCE).contains(&frames) {
+                        return Err("check distance must be between 1 and 7");
+                    }
+                    cfg.check_distance = CheckDistance(frames);
+                }
+                "-n" | "--name" => {
+                    let name = args.next().ok_or("missing player name")?;
+                    cfg.name = PlayerName(name.clone());
+                }
+                "-c" | "--class" => {
+                    let class = args.next().ok_or("missing class name")?;
+                    cfg.class = class.parse()?;
+                }
+                "-s" | "--server" => {
+                    let server = args.next().ok_or("missing server url")?;
+                    cfg.matchmaking.server = server.clone();
+                }
+                "-r" | "--room" => {
+                    let room = args.next().ok_or("missing room name")?;
+                    cfg.matchmaking.room = room.clone();
+                }
+                "-p" | "--players" => {
+                    let players = args.next().ok_or("missing player count")?;
+                    let players = players.parse().map_err(|_| "invalid player count")?;
+                    if !(MIN_PLAYERS..=MAX_PLAYERS).contains(&players) {
+                        return Err("player count must be between 2 and 4");
+                    }
+                    cfg.matchmaking.players = players;
+                }
+                "--spectators" => {
+                    let spectators = args.next().ok_or("missing spectator count")?;
+                    cfg.matchmaking.spectators =
+                        spectators.parse().map_err(|_| "invalid spectator count")?;
+                }
+                "--host" => {
+                    host = true;
+                }
+                "--join" => {
+                    let address = args.next().ok_or("missing host address")?;
+                    join = Some(address.clone());
+                }
+                "--port" => {
+                    let number = args.next().ok_or("missing port")?;
+                    port = Some(number.parse().map_err(|_| "invalid port")?);
+                }
+                "--spectate" => {
+                    cfg.matchmaking.spectate = true;
+                }
                 _ => return Err("unknown argument"),
             }
         }
 
+        if port.is_some() && !host && join.is_none() {
+            return Err("--port needs --host or --join");
+        }
+        match (host, join) {
+            (true, Some(_)) => return Err("can't both host and join"),
+            (true, None) => {
+                let port = port.unwrap_or(DEFAULT_SIGNALING_PORT);
+                cfg.host = Some(port);
+                cfg.matchmaking.server = format!("ws://127.0.0.1:{port}");
+            }
+            (false, Some(address)) => {
+                let address = match port {
+                    Some(_) if address.contains(':') => {
+                        return Err("--port with a host address that has a port already")
+                    }
+                    Some(port) => format!("{address}:{port}"),
+                    None => address,
+                };
+                cfg.matchmaking.server = host_server(&address);
+            }
+            (false, None) => {}
+        }
+
+        if cfg.matchmaking.spectate {
+            cfg.matchmaking.spectators = usize::max(cfg.matchmaking.spectators, 1);
+        }
+
         Ok(cfg)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build(args: &[&str]) -> Result<ProgramConfig, &'static str> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        ProgramConfig::build(&args)
+    }
+
+    #[test]
+    fn options() {
+        let cfg = build(&["--solo", "-p", "3", "--spectate", "-n", "Ann"]).unwrap();
+        assert_eq!(cfg.mode, GameMode::Solo);
+        assert_eq!(cfg.matchmaking.players, 3);
+        assert_eq!(cfg.matchmaking.spectators, 1);
+        assert_eq!(cfg.name.0, "Ann");
+    }
+
+    #[test]
+    fn bad_arguments() {
+        assert_eq!(
+            build(&["-p", "5"]).err(),
+            Some("player count must be between 2 and 4")
+        );
+        assert_eq!(
+            build(&["--check-distance", "8"]).err(),
+            Some("check distance must be between 1 and 7")
+        );
+        assert_eq!(build(&["--nope"]).err(), Some("unknown argument"));
+    }
+
+    #[test]
+    fn hosting_and_joining() {
+        let cfg = build(&["--host", "--port", "4000"]).unwrap();
+        assert_eq!(cfg.host, Some(4000));
+        assert_eq!(cfg.matchmaking.server, "ws://127.0.0.1:4000");
+
+        let cfg = build(&["--join", "192.168.1.5"]).unwrap();
+        assert_eq!(cfg.host, None);
+        assert_eq!(cfg.matchmaking.server, "ws://192.168.1.5:3536");
+
+        let cfg = build(&["--join", "192.168.1.5", "--port", "4000"]).unwrap();
+        assert_eq!(cfg.matchmaking.server, "ws://192.168.1.5:4000");
+
+        let cfg = build(&["--join", "192.168.1.5:4000"]).unwrap();
+        assert_eq!(cfg.matchmaking.server, "ws://192.168.1.5:4000");
+
+        assert_eq!(
+            build(&["--join", "192.168.1.5:4000", "--port", "5000"]).err(),
+            Some("--port with a host address that has a port already")
+        );
+
+        assert_eq!(
+            build(&["--port", "4000"]).err(),
+            Some("--port needs --host or --join")
+        );
+        assert_eq!(
+            build(&["--host", "--join", "a"]).err(),
+            Some("can't both host and join")
+        );
+        assert_eq!(
+            build(&["--port", "x", "--host"]).err(),
+            Some("invalid port")
+        );
+        assert_eq!(build(&["--join"]).err(), Some("missing host address"));
+    }
+}
@@ -0,0 +1,307 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt};
+
+/**
+ * The controls file, kept next to the settings in the game's folder of the user's config directory.
+ */
+pub const CONTROLS_FILE: &str = "controls.ron";
+
+/**
+ * Keys the game already uses for pausing, chat, the overlays and replay controls.
+ */
+pub const RESERVED_KEYS: [KeyCode; 7] = [
+    KeyCode::Escape,
+    KeyCode::Return,
+    KeyCode::F3,
+    KeyCode::F12,
+    KeyCode::P,
+    KeyCode::LBracket,
+    KeyCode::RBracket,
+];
+
+/**
+ * Everything the local player can do with a key or mouse button.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Action {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    Attack,
+    Secondary,
+    Interact,
+}
+
+impl Action {
+    pub const ALL: [Action; 7] = [
+        Action::MoveUp,
+        Action::MoveDown,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::Attack,
+        Action::Secondary,
+        Action::Interact,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::MoveUp => "Move up",
+            Action::MoveDown => "Move down",
+            Action::MoveLeft => "Move left",
+            Action::MoveRight => "Move right",
+            Action::Attack => "Attack",
+            Action::Secondary => "Ability",
+            Action::Interact => "Interact",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+}
+
+impl Binding {
+    pub fn is_reserved(&self) -> bool {
+        matches!(self, Binding::Key(key) if RESERVED_KEYS.contains(key))
+    }
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Binding::Key(key) => write!(f, "{key:?}"),
+            Binding::Mouse(button) => write!(f, "Mouse {button:?}"),
+        }
+    }
+}
+
+/**
+ * The keys and mouse buttons bound to each action, any of them triggers it.
+ */
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Bindings(pub BTreeMap<Action, Vec<Binding>>);
+
+impl Default for Bindings {
+    fn default() -> Self {
+        use Binding::{Key, Mouse};
+        Bindings(BTreeMap::from([
+            (Action::MoveUp, vec![Key(KeyCode::Up), Key(KeyCode::W)]),
+            (Action::MoveDown, vec![Key(KeyCode::Down), Key(KeyCode::S)]),
+            (Action::MoveLeft, vec![Key(KeyCode::Left), Key(KeyCode::A)]),
+            (
+                Action::MoveRight,
+                vec![Key(KeyCode::Right), Key(KeyCode::D)],
+            ),
+            (
+                Action::Attack,
+                vec![
+                    Key(KeyCode::Space),
+                    Key(KeyCode::J),
+                    Mouse(MouseButton::Left),
+                ],
+            ),
+            (
+                Action::Secondary,
+                vec![
+                    Key(KeyCode::K),
+                    Key(KeyCode::LShift),
+                    Mouse(MouseButton::Right),
+                ],
+            ),
+            (Action::Interact, vec![Key(KeyCode::E)]),
+        ]))
+    }
+}
+
+impl Bindings {
+    pub fn get(&self, action: Action) -> &[Binding] {
+        self.0.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn pressed(
+        &self,
+        action: Action,
+        keys: &Input<KeyCode>,
+        mouse: &Input<MouseButton>,
+    ) -> bool {
+        self.get(action).iter().any(|binding| match binding {
+            Binding::Key(key) => keys.pressed(*key),
+            Binding::Mouse(button) => mouse.pressed(*button),
+        })
+    }
+
+    /**
+     * Puts the binding in a slot of the action, replacing what was there. Slots past the last
+     * binding add it instead.
+     */
+    pub fn set(&mut self, action: Action, slot: usize, binding: Binding) {
+        let bindings = self.0.entry(action).or_default();
+        match bindings.get_mut(slot) {
+            Some(old) => *old = binding,
+            None => bindings.push(binding),
+        }
+    }
+
+    /**
+     * Removes the binding in a slot of the action.
+     */
+    pub fn remove(&mut self, action: Action, slot: usize) {
+        if let Some(bindings) = self.0.get_mut(&action) {
+            if slot < bindings.len() {
+                bindings.remove(slot);
+            }
+        }
+    }
+
+    /**
+     * The other action the binding already triggers, if any.
+     */
+    pub fn conflict(&self, action: Action, binding: Binding) -> Option<Action> {
+        self.0
+            .iter()
+            .find(|(other, bindings)| **other != action && bindings.contains(&binding))
+            .map(|(other, _)| *other)
+    }
+
+    /**
+     * Every pair of actions sharing a binding.
+     */
+    pub fn conflicts(&self) -> Vec<(Action, Action, Binding)> {
+        self.0
+            .iter()
+            .flat_map(|(action, bindings)| {
+                bindings.iter().filter_map(move |binding| {
+                    self.conflict(*action, *binding)
+                        .filter(|other| action < other)
+                        .map(|other| (*action, other, *binding))
+                })
+            })
+            .collect()
+    }
+
+    /**
+     * Drops bindings an action has more than once, keeping the first.
+     */
+    fn deduplicated(mut self) -> Bindings {
+        for bindings in self.0.values_mut() {
+            let mut seen = Vec::new();
+            bindings.retain(|binding| {
+                let new = !seen.contains(binding);
+                seen.push(*binding);
+                new
+            });
+        }
+        self
+    }
+
+    /**
+     * The saved controls, or the defaults when there are none or they don't make sense.
+     */
+    pub fn load() -> Bindings {
+        let Some(text) = read_controls() else {
+            return Bindings::default();
+        };
+        let mut bindings = match ron::from_str::<Bindings>(&text) {
+            Ok(bindings) => bindings.deduplicated(),
+            Err(err) => {
+                warn!("Ignoring {}: {}", CONTROLS_FILE, err);
+                return Bindings::default();
+            }
+        };
+
+        let conflicts = bindings.conflicts();
+        if !conflicts.is_empty() {
+            for (action, other, binding) in conflicts {
+                warn!(
+                    "{} is bound to both {} and {} in {}",
+                    binding,
+                    action.name(),
+                    other.name(),
+                    CONTROLS_FILE
+                );
+            }
+            return Bindings::default();
+        }
+
+        let reserved = bindings
+            .0
+            .values()
+            .flatten()
+            .find(|binding| binding.is_reserved());
+        if let Some(binding) = reserved {
+            warn!("{} is reserved but bound in {}", binding, CONTROLS_FILE);
+            return Bindings::default();
+        }
+
+        // actions added since the file was written keep their default bindings
+        for (action, defaults) in Bindings::default().0 {
+            bindings.0.entry(action).or_insert(defaults);
+        }
+        bindings
+    }
+
+    pub fn save(&self) {
+        match ron::ser::to_string_pretty(self, default()) {
+            Ok(text) => write_controls(&text),
+            Err(err) => error!("Failed to serialize the controls: {}", err),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read_controls() -> Option<String> {
+    crate::settings::components::read_config(CONTROLS_FILE)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write_controls(text: &str) {
+    crate::settings::components::write_config(CONTROLS_FILE, text);
+}
+
+#[cfg(target_arch = "wasm32")]
+fn read_controls() -> Option<String> {
+    None
+}
+
+#[cfg(target_arch = "wasm32")]
+fn write_controls(_: &str) {
+    warn!("Controls can't be saved in the browser");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_have_no_conflicts() {
+        assert!(Bindings::default().conflicts().is_empty());
+    }
+
+    #[test]
+    fn shared_binding_conflicts_once() {
+        let mut bindings = Bindings::default();
+        bindings.set(Action::Secondary, 3, Binding::Key(KeyCode::E));
+        assert_eq!(
+            bindings.conflicts(),
+            vec![(
+                Action::Secondary,
+                Action::Interact,
+                Binding::Key(KeyCode::E)
+            )]
+        );
+        bindings.remove(Action::Secondary, 3);
+        assert!(bindings.conflicts().is_empty());
+    }
+
+    #[test]
+    fn duplicates_within_an_action_are_dropped() {
+        let mut bindings = Bindings::default();
+        bindings.set(Action::Attack, 3, Binding::Key(KeyCode::J));
+        bindings.set(Action::Attack, 4, Binding::Key(KeyCode::Space));
+        assert_eq!(bindings.deduplicated(), Bindings::default());
+    }
+}
@@ -0,0 +1,20 @@
+use bevy::prelude::{Component, Resource};
+
+#[derive(Component)]
+pub struct ControlsText;
+
+#[derive(Component)]
+pub struct BindingListText;
+
+/**
+ * The action and binding highlighted on the controls screen, and whether it's waiting for a new
+ * binding.
+ */
+#[derive(Resource, Default)]
+pub struct Rebinding {
+    pub selected: usize,
+    /// Which of the action's bindings, one past the last adds a new one.
+    pub slot: usize,
+    pub waiting: bool,
+    pub message: Option<String>,
+}
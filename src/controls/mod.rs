@@ -0,0 +1,22 @@
+use self::systems::{controls_input, setup, teardown, update_binding_list};
+use crate::GameState;
+use bevy::prelude::*;
+pub mod bindings;
+mod components;
+mod systems;
+
+/**
+ * Screen for rebinding the keys of every action, reached from the main menu.
+ */
+pub struct ControlsPlugin;
+impl Plugin for ControlsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(setup.in_schedule(OnEnter(GameState::Controls)))
+            .add_systems(
+                (controls_input, update_binding_list)
+                    .chain()
+                    .in_set(OnUpdate(GameState::Controls)),
+            )
+            .add_system(teardown.in_schedule(OnExit(GameState::Controls)));
+    }
+}
@@ -0,0 +1,206 @@
+use bevy::prelude::*;
+
+use crate::{FontAssets, GameState};
+
+use super::{
+    bindings::{Action, Binding, Bindings},
+    components::{BindingListText, ControlsText, Rebinding},
+};
+
+pub fn setup(mut commands: Commands, fonts: Res<FontAssets>) {
+    commands.init_resource::<Rebinding>();
+
+    commands.spawn((
+        TextBundle::from_sections([TextSection::new(
+            "Controls",
+            TextStyle {
+                font: fonts.visitor.clone(),
+                font_size: 40.0,
+                color: Color::WHITE,
+            },
+        )])
+        .with_style(Style {
+            margin: UiRect {
+                top: Val::Px(32.0),
+                right: Val::Auto,
+                left: Val::Auto,
+                ..default()
+            },
+            ..default()
+        }),
+        ControlsText,
+    ));
+    commands.spawn((
+        TextBundle::from_sections([TextSection::new(
+            "",
+            TextStyle {
+                font: fonts.visitor.clone(),
+                font_size: 20.0,
+                color: Color::WHITE,
+            },
+        )])
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                top: Val::Percent(20.0),
+                ..default()
+            },
+            margin: UiRect {
+                left: Val::Auto,
+                right: Val::Auto,
+                ..default()
+            },
+            ..default()
+        }),
+        BindingListText,
+        ControlsText,
+    ));
+}
+
+/**
+ * Up and down pick an action and left and right one of its bindings, enter replaces it with the
+ * next key or mouse button pressed.
+ */
+pub fn controls_input(
+    keys: Res<Input<KeyCode>>,
+    mouse: Res<Input<MouseButton>>,
+    mut bindings: ResMut<Bindings>,
+    mut rebinding: ResMut<Rebinding>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let action = Action::ALL[rebinding.selected];
+
+    if rebinding.waiting {
+        if keys.just_pressed(KeyCode::Back) {
+            rebinding.waiting = false;
+            rebinding.message = None;
+            return;
+        }
+        let pressed = keys
+            .get_just_pressed()
+            .next()
+            .map(|key| Binding::Key(*key))
+            .or_else(|| {
+                mouse
+                    .get_just_pressed()
+                    .next()
+                    .map(|button| Binding::Mouse(*button))
+            });
+        let Some(binding) = pressed else {
+            return;
+        };
+
+        rebinding.waiting = false;
+        rebinding.message = if binding.is_reserved() {
+            Some(format!("{binding} is reserved by the game"))
+        } else if bindings.get(action).contains(&binding) {
+            Some(format!("{} is already bound to {}", binding, action.name()))
+        } else if let Some(other) = bindings.conflict(action, binding) {
+            Some(format!("{} is already bound to {}", binding, other.name()))
+        } else {
+            bindings.set(action, rebinding.slot, binding);
+            None
+        };
+        return;
+    }
+
+    // the slot after the last binding adds a new one
+    let slots = bindings.get(action).len() + 1;
+
+    if keys.just_pressed(KeyCode::Up) {
+        rebinding.selected = rebinding
+            .selected
+            .checked_sub(1)
+            .unwrap_or(Action::ALL.len() - 1);
+        rebinding.slot = 0;
+        rebinding.message = None;
+    } else if keys.just_pressed(KeyCode::Down) {
+        rebinding.selected = (rebinding.selected + 1) % Action::ALL.len();
+        rebinding.slot = 0;
+        rebinding.message = None;
+    } else if keys.just_pressed(KeyCode::Left) {
+        rebinding.slot = rebinding.slot.checked_sub(1).unwrap_or(slots - 1);
+        rebinding.message = None;
+    } else if keys.just_pressed(KeyCode::Right) {
+        rebinding.slot = (rebinding.slot + 1) % slots;
+        rebinding.message = None;
+    } else if keys.just_pressed(KeyCode::Delete) {
+        rebinding.message = if slots <= 2 {
+            Some(format!("{} needs a binding", action.name()))
+        } else {
+            bindings.remove(action, rebinding.slot);
+            rebinding.slot = rebinding.slot.min(slots - 2);
+            None
+        };
+    } else if keys.just_pressed(KeyCode::Return) {
+        rebinding.waiting = true;
+        rebinding.message = Some(format!(
+            "Press a key for {}, backspace cancels",
+            action.name()
+        ));
+    } else if keys.just_pressed(KeyCode::R) {
+        *bindings = Bindings::default();
+        rebinding.slot = 0;
+        rebinding.message = Some("Restored the default controls".into());
+    } else if keys.just_pressed(KeyCode::Tab) {
+        next_state.set(GameState::MainMenu);
+    }
+}
+
+pub fn update_binding_list(
+    bindings: Res<Bindings>,
+    rebinding: Res<Rebinding>,
+    mut texts: Query<&mut Text, With<BindingListText>>,
+) {
+    if !bindings.is_changed() && !rebinding.is_changed() {
+        return;
+    }
+
+    let mut lines: Vec<_> = Action::ALL
+        .iter()
+        .enumerate()
+        .map(|(i, action)| {
+            let selected = i == rebinding.selected;
+            let cursor = if selected { ">" } else { " " };
+            let mut keys: Vec<_> = bindings
+                .get(*action)
+                .iter()
+                .map(|binding| binding.to_string())
+                .collect();
+            if selected {
+                keys.push("+".into());
+                if let Some(key) = keys.get_mut(rebinding.slot) {
+                    *key = format!("[{key}]");
+                }
+                if rebinding.slot + 1 < keys.len() {
+                    keys.pop();
+                }
+            }
+            format!("{} {}: {}", cursor, action.name(), keys.join(", "))
+        })
+        .collect();
+    lines.push(String::new());
+    lines.push(rebinding.message.clone().unwrap_or_else(|| {
+        "< > to pick a binding, enter to change it\nDelete to remove it, R to reset\nTab to go back"
+            .into()
+    }));
+
+    for mut text in texts.iter_mut() {
+        text.sections[0].value = lines.join("\n");
+    }
+}
+
+/**
+ * Leaving the screen saves the controls for the next time the game starts.
+ */
+pub fn teardown(
+    mut commands: Commands,
+    bindings: Res<Bindings>,
+    texts: Query<(Entity, With<ControlsText>)>,
+) {
+    for (entity, _) in texts.iter() {
+        commands.entity(entity).despawn();
+    }
+    commands.remove_resource::<Rebinding>();
+    bindings.save();
+}
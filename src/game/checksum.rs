@@ -0,0 +1,335 @@
+use bevy::{prelude::*, utils::HashMap};
+use bevy_ggrs::{PlayerInputs, Rollback, Session};
+use bevy_matchbox::{
+    prelude::{MultipleChannels, PeerId},
+    MatchboxSocket,
+};
+use bevy_turborand::{DelegatedRng, RngComponent};
+use std::fmt::Write;
+
+use super::{
+    classes::{AbilityCooldown, Dashing, Facing, Projectile},
+    combat::AttackCooldown,
+    components::{FrameCount, GgrsConfig, SpawnOrder},
+    effects::Flick,
+    fnv::Fnv64,
+    input::PlayerInput,
+    loot::{Chest, Item},
+    protocol::{broadcast, send, PeerMessage, ReceivedMessage},
+    stats::{Experience, Health, Stats},
+    status::StatusEffects,
+};
+
+/**
+ * Frames of checksums kept around, peers need to send theirs before they're forgotten.
+ */
+pub const CHECKSUM_HISTORY: u32 = 128;
+
+/**
+ * The world at the end of a simulated frame.
+ */
+pub struct FrameRecord {
+    pub checksum: u64,
+    /// Every rollback entity's state, written to the desync report.
+    pub dump: String,
+    pub inputs: Vec<PlayerInput>,
+}
+
+/**
+ * Hash of the whole simulation state at the end of the last frame. Sync tests only compare
+ * components and resources reflecting `Hash`, rolling this back lets them check everything the
+ * desync reports do.
+ */
+#[derive(Resource, Reflect, Default, Hash, Clone, Copy)]
+#[reflect(Resource, Hash)]
+pub struct StateChecksum(pub u64);
+
+#[derive(Resource, Default)]
+pub struct ChecksumHistory {
+    local: HashMap<u32, FrameRecord>,
+    remote: HashMap<u32, Vec<(PeerId, u64)>>,
+    last_sent: u32,
+    /// Only the first desync gets reported, every frame after it is bound to differ too.
+    desynced: bool,
+    /// The desynced frame, the peer and its checksum, until the peer sends its dump.
+    awaiting_dump: Option<(u32, PeerId, u64)>,
+}
+
+/**
+ * Counts simulated frames, first thing in the GGRS schedule.
+ */
+pub fn count_frames(mut frame: ResMut<FrameCount>) {
+    frame.0 += 1;
+}
+
+/**
+ * Gives rollback entities that just showed up their [`SpawnOrder`]. Entities spawned again after a
+ * rollback get new ids, but in the same order as the first time, so their rank by id is stable.
+ */
+pub fn order_spawns(
+    mut commands: Commands,
+    frame: Res<FrameCount>,
+    spawned: Query<(Entity, &Rollback), Without<SpawnOrder>>,
+) {
+    let mut spawned: Vec<_> = spawned.iter().collect();
+    spawned.sort_by_key(|(_, rollback)| rollback.id());
+    for (index, (entity, _)) in spawned.into_iter().enumerate() {
+        commands.entity(entity).insert(SpawnOrder {
+            frame: frame.0,
+            index: index as u32,
+        });
+    }
+}
+
+/**
+ * Records the state of every rollback entity, last thing in the GGRS schedule. Cameras, the UI and
+ * the tilemap aren't simulated, they are left out.
+ *
+ * Frames simulated again after a rollback overwrite their earlier record.
+ */
+#[allow(clippy::type_complexity)]
+pub fn record_checksum(
+    frame: Res<FrameCount>,
+    inputs: Res<PlayerInputs<GgrsConfig>>,
+    mut history: ResMut<ChecksumHistory>,
+    mut state_checksum: ResMut<StateChecksum>,
+    query: Query<
+        (
+            &SpawnOrder,
+            Option<&Transform>,
+            Option<&Health>,
+            Option<&Stats>,
+            Option<&Experience>,
+            Option<&StatusEffects>,
+            Option<&AttackCooldown>,
+            Option<&AbilityCooldown>,
+            Option<&Dashing>,
+            Option<&Facing>,
+            Option<&Projectile>,
+            Option<&Flick>,
+            Option<&Chest>,
+            Option<&Item>,
+            Option<&RngComponent>,
+        ),
+        With<Rollback>,
+    >,
+) {
+    let mut entities: Vec<_> = query.iter().collect();
+    entities.sort_by_key(|(order, ..)| **order);
+
+    let mut dump = String::new();
+    for (
+        order,
+        transform,
+        health,
+        stats,
+        experience,
+        effects,
+        attack_cooldown,
+        ability_cooldown,
+        dashing,
+        facing,
+        projectile,
+        flick,
+        chest,
+        item,
+        rng,
+    ) in entities
+    {
+        // the rng can't be printed, a number drawn from a copy of it tells just as much
+        let rng = rng.map(|rng| rng.clone().u64(..));
+        let _ = writeln!(
+            dump,
+            "{}.{}: {:?} {:?} {:?} {:?} {:?} {:?} {:?} {:?} {:?} {:?} {:?} {:?} {:?} {:?}",
+            order.frame,
+            order.index,
+            transform.map(|t| (t.translation, t.rotation, t.scale)),
+            health,
+            stats,
+            experience,
+            effects,
+            attack_cooldown,
+            ability_cooldown,
+            dashing,
+            facing,
+            projectile,
+            flick,
+            chest,
+            item,
+            rng,
+        );
+    }
+
+    state_checksum.0 = Fnv64::hash_bytes(dump.as_bytes());
+
+    history.local.insert(
+        frame.0,
+        FrameRecord {
+            checksum: state_checksum.0,
+            dump,
+            inputs: inputs.iter().map(|(input, _)| *input).collect(),
+        },
+    );
+}
+
+/**
+ * Sends the checksums of confirmed frames to every peer and compares them with theirs.
+ */
+pub fn exchange_checksums(
+    session: Option<Res<Session<GgrsConfig>>>,
+    socket: Option<ResMut<MatchboxSocket<MultipleChannels>>>,
+    mut messages: EventReader<ReceivedMessage>,
+    mut history: ResMut<ChecksumHistory>,
+) {
+    let (Some(Session::P2PSession(session)), Some(mut socket)) = (session.as_deref(), socket)
+    else {
+        return;
+    };
+
+    for ReceivedMessage { peer, message } in messages.iter() {
+        match message {
+            PeerMessage::Checksum { frame, checksum } => {
+                history
+                    .remote
+                    .entry(*frame)
+                    .or_default()
+                    .push((*peer, *checksum));
+            }
+            PeerMessage::DumpRequest { frame } => {
+                if let Some(record) = history.local.get(frame) {
+                    let dump = PeerMessage::Dump {
+                        frame: *frame,
+                        dump: record.dump.clone(),
+                    };
+                    send(&mut socket, *peer, &dump);
+                }
+            }
+            PeerMessage::Dump { frame, dump } => {
+                let Some((desync_frame, desync_peer, checksum)) = history.awaiting_dump else {
+                    continue;
+                };
+                if desync_frame != *frame || desync_peer != *peer {
+                    continue;
+                }
+                history.awaiting_dump = None;
+                if let Some(record) = history.local.get(frame) {
+                    let remote = RemoteRecord {
+                        peer: *peer,
+                        checksum,
+                        dump: Some(dump),
+                    };
+                    write_desync_report(*frame, record, &remote);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // frames up to the confirmed one won't be simulated again
+    let Ok(confirmed) = u32::try_from(session.confirmed_frame()) else {
+        return;
+    };
+
+    for frame in history.last_sent + 1..=confirmed {
+        if let Some(record) = history.local.get(&frame) {
+            let message = PeerMessage::Checksum {
+                frame,
+                checksum: record.checksum,
+            };
+            broadcast(&mut socket, &message);
+        }
+    }
+    history.last_sent = u32::max(history.last_sent, confirmed);
+
+    if !history.desynced {
+        // the earliest mismatch is the one worth reporting, the later ones follow from it
+        let mut frames: Vec<_> = history
+            .remote
+            .keys()
+            .copied()
+            .filter(|frame| *frame <= confirmed)
+            .collect();
+        frames.sort_unstable();
+        let mismatch = frames.into_iter().find_map(|frame| {
+            let record = history.local.get(&frame)?;
+            let (peer, checksum) = history.remote[&frame]
+                .iter()
+                .find(|(_, c)| *c != record.checksum)?;
+            Some((frame, *peer, *checksum))
+        });
+        if let Some((frame, peer, checksum)) = mismatch {
+            history.desynced = true;
+            history.awaiting_dump = Some((frame, peer, checksum));
+            send(&mut socket, peer, &PeerMessage::DumpRequest { frame });
+
+            // written again along with their side once the peer answers
+            let remote = RemoteRecord {
+                peer,
+                checksum,
+                dump: None,
+            };
+            write_desync_report(frame, &history.local[&frame], &remote);
+        }
+    }
+
+    // forget frames nobody is going to ask about anymore, except the one we want the dump of
+    let oldest = confirmed.saturating_sub(CHECKSUM_HISTORY);
+    let awaiting = history.awaiting_dump.map(|(frame, ..)| frame);
+    history
+        .local
+        .retain(|frame, _| *frame >= oldest || Some(*frame) == awaiting);
+    history.remote.retain(|frame, _| *frame >= oldest);
+}
+
+/**
+ * The other side of a desync.
+ */
+struct RemoteRecord<'a> {
+    peer: PeerId,
+    checksum: u64,
+    /// Their dump of the frame, once they've sent it.
+    dump: Option<&'a str>,
+}
+
+fn desync_report(frame: u32, record: &FrameRecord, remote: &RemoteRecord) -> String {
+    let RemoteRecord {
+        peer,
+        checksum,
+        dump,
+    } = remote;
+    format!(
+        "desync at frame {frame}\n\
+         local checksum: {:016x}\n\
+         {peer:?} checksum: {checksum:016x}\n\
+         inputs: {:?}\n\n\
+         local state:\n{}\n\
+         {peer:?} state:\n{}",
+        record.checksum,
+        record.inputs,
+        record.dump,
+        dump.unwrap_or("not received yet\n"),
+    )
+}
+
+/**
+ * Writes both sides of the desync to the working directory.
+ */
+#[cfg(not(target_arch = "wasm32"))]
+fn write_desync_report(frame: u32, record: &FrameRecord, remote: &RemoteRecord) {
+    let path = format!("desync-frame-{frame}.log");
+    match std::fs::write(&path, desync_report(frame, record, remote)) {
+        Ok(()) => error!(
+            "Desync with {:?} at frame {}, wrote {}",
+            remote.peer, frame, path
+        ),
+        Err(err) => error!(
+            "Desync at frame {}, failed writing {}: {}",
+            frame, path, err
+        ),
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn write_desync_report(frame: u32, record: &FrameRecord, remote: &RemoteRecord) {
+    error!("{}", desync_report(frame, record, remote));
+}
@@ -0,0 +1,352 @@
+use bevy::prelude::*;
+use bevy_ggrs::{PlayerInputs, RollbackIdProvider};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+use crate::ImageAssets;
+
+use super::{
+    collision::{circles_touching, distance_between},
+    combat::{AttackCooldown, Enemy, LastHitBy},
+    components::{Bounding, GgrsConfig, Player, SpawnOrder},
+    stats::{Experience, Health, Stats},
+    status::{StatusEffects, StatusKind},
+};
+
+pub const FIRE_BOLT_SPRITE: usize = 632;
+pub const FIRE_BOLT_SPEED: f32 = 5.0;
+pub const FIRE_BOLT_FRAMES: u32 = 60;
+pub const CLEAVE_REACH: f32 = 40.0;
+pub const DASH_FRAMES: u32 = 10;
+pub const DASH_SPEED_MULTIPLIER: f32 = 3.0;
+pub const CLEAVE_STUN_FRAMES: u32 = 45;
+pub const FIRE_BOLT_BURN_FRAMES: u32 = 180;
+
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize, Reflect, FromReflect,
+)]
+pub enum CharacterClass {
+    #[default]
+    Warrior,
+    Ranger,
+    Mage,
+}
+
+impl CharacterClass {
+    pub const ALL: [CharacterClass; 3] = [
+        CharacterClass::Warrior,
+        CharacterClass::Ranger,
+        CharacterClass::Mage,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CharacterClass::Warrior => "Warrior",
+            CharacterClass::Ranger => "Ranger",
+            CharacterClass::Mage => "Mage",
+        }
+    }
+
+    pub fn base_stats(&self) -> Stats {
+        match self {
+            CharacterClass::Warrior => Stats::new(7, 4, 7, 2),
+            CharacterClass::Ranger => Stats::new(4, 7, 5, 4),
+            CharacterClass::Mage => Stats::new(2, 5, 4, 9),
+        }
+    }
+
+    pub fn ability(&self) -> Ability {
+        match self {
+            CharacterClass::Warrior => Ability::Cleave,
+            CharacterClass::Ranger => Ability::Dash,
+            CharacterClass::Mage => Ability::FireBolt,
+        }
+    }
+
+    /**
+     * Status effect applied by basic attacks and how many frames it lasts.
+     */
+    pub fn on_hit(&self) -> Option<(StatusKind, u32)> {
+        match self {
+            CharacterClass::Ranger => Some((StatusKind::Slow, 90)),
+            _ => None,
+        }
+    }
+
+    pub fn atlas(&self, images: &ImageAssets) -> Handle<TextureAtlas> {
+        match self {
+            CharacterClass::Warrior => images.warrior.clone(),
+            CharacterClass::Ranger => images.ranger.clone(),
+            CharacterClass::Mage => images.mage.clone(),
+        }
+    }
+
+    pub fn next(&self) -> CharacterClass {
+        let idx = CharacterClass::ALL.iter().position(|c| c == self).unwrap();
+        CharacterClass::ALL[(idx + 1) % CharacterClass::ALL.len()]
+    }
+
+    pub fn previous(&self) -> CharacterClass {
+        let idx = CharacterClass::ALL.iter().position(|c| c == self).unwrap();
+        CharacterClass::ALL[(idx + CharacterClass::ALL.len() - 1) % CharacterClass::ALL.len()]
+    }
+}
+
+impl FromStr for CharacterClass {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        CharacterClass::ALL
+            .into_iter()
+            .find(|c| c.name().eq_ignore_ascii_case(s))
+            .ok_or("unknown class")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ability {
+    /// Hits every enemy around the player for double damage.
+    Cleave,
+    /// A short burst of speed in the direction the player is facing.
+    Dash,
+    /// Shoots a projectile dealing spell damage to the first enemy it touches.
+    FireBolt,
+}
+
+impl Ability {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Ability::Cleave => "Cleave",
+            Ability::Dash => "Dash",
+            Ability::FireBolt => "Fire bolt",
+        }
+    }
+
+    /**
+     * Frames until the ability can be used again.
+     */
+    pub fn cooldown(&self) -> u32 {
+        match self {
+            Ability::Cleave => 90,
+            Ability::Dash => 60,
+            Ability::FireBolt => 45,
+        }
+    }
+}
+
+/**
+ * The class chosen by the local player before the match.
+ */
+#[derive(Resource, Default)]
+pub struct SelectedClass(pub CharacterClass);
+
+/**
+ * Every player's class, indexed by their GGRS player handle.
+ */
+#[derive(Resource, Default)]
+pub struct PlayerClasses(pub Vec<CharacterClass>);
+
+#[derive(Debug, Component, Reflect, Default, Hash)]
+#[reflect(Component, Hash)]
+pub struct Class(pub CharacterClass);
+
+#[derive(Debug, Component, Reflect, Default, Hash)]
+#[reflect(Component, Hash)]
+pub struct AbilityCooldown(pub u32);
+
+#[derive(Debug, Component, Reflect)]
+#[reflect(Component)]
+pub struct Facing(pub Vec2);
+
+impl Default for Facing {
+    fn default() -> Self {
+        Facing(Vec2::X)
+    }
+}
+
+/**
+ * Frames left of an ongoing dash.
+ */
+#[derive(Debug, Component, Reflect, Default, Hash)]
+#[reflect(Component, Hash)]
+pub struct Dashing(pub u32);
+
+#[derive(Debug, Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct Projectile {
+    pub velocity: Vec2,
+    pub damage: u32,
+    pub owner: usize,
+    pub frames_left: u32,
+    pub effect: Option<(StatusKind, u32)>,
+}
+
+/**
+ * Everything a player character needs to fight, based on their class.
+ */
+#[derive(Bundle, Default)]
+pub struct CharacterBundle {
+    pub class: Class,
+    pub stats: Stats,
+    pub health: Health,
+    pub experience: Experience,
+    pub attack_cooldown: AttackCooldown,
+    pub ability_cooldown: AbilityCooldown,
+    pub facing: Facing,
+    pub dashing: Dashing,
+    pub status_effects: StatusEffects,
+}
+
+impl CharacterBundle {
+    pub fn new(class: CharacterClass) -> CharacterBundle {
+        let stats = class.base_stats();
+        CharacterBundle {
+            class: Class(class),
+            stats,
+            health: Health::full(stats.max_health()),
+            ..default()
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub fn use_abilities(
+    mut commands: Commands,
+    mut rip: ResMut<RollbackIdProvider>,
+    images: Res<ImageAssets>,
+    inputs: Res<PlayerInputs<GgrsConfig>>,
+    mut players: Query<(
+        &Player,
+        &Class,
+        &Stats,
+        &Transform,
+        &Bounding,
+        &Facing,
+        &StatusEffects,
+        &mut AbilityCooldown,
+        &mut Dashing,
+    )>,
+    mut enemies: Query<
+        (
+            &Transform,
+            &Bounding,
+            &mut Health,
+            &mut LastHitBy,
+            &mut StatusEffects,
+        ),
+        (With<Enemy>, Without<Player>),
+    >,
+) {
+    // cleaves overwrite the last hit and fire bolts take rollback ids, players take turns by handle
+    let mut players: Vec<_> = players.iter_mut().collect();
+    players.sort_by_key(|(player, ..)| player.handle);
+
+    for (player, class, stats, transform, bounding, facing, effects, mut cooldown, mut dashing) in
+        players
+    {
+        dashing.0 = dashing.0.saturating_sub(1);
+        if cooldown.0 > 0 {
+            cooldown.0 -= 1;
+            continue;
+        }
+        if effects.is_stunned() {
+            continue;
+        }
+
+        let (input, _) = inputs[player.handle];
+        if !input.secondary() {
+            continue;
+        }
+
+        let ability = class.0.ability();
+        cooldown.0 = ability.cooldown();
+        match ability {
+            Ability::Cleave => {
+                for (enemy_transform, enemy_bounding, mut health, mut last_hit_by, mut effects) in
+                    enemies.iter_mut()
+                {
+                    let distance =
+                        distance_between(&transform.translation, &enemy_transform.translation);
+                    if distance <= bounding.0 + enemy_bounding.0 + CLEAVE_REACH {
+                        health.damage(stats.attack_damage() * 2);
+                        last_hit_by.0 = Some(player.handle);
+                        effects.apply(StatusKind::Stun, CLEAVE_STUN_FRAMES, Some(player.handle));
+                    }
+                }
+            }
+            Ability::Dash => {
+                dashing.0 = DASH_FRAMES;
+            }
+            Ability::FireBolt => {
+                commands.spawn((
+                    SpriteSheetBundle {
+                        texture_atlas: images.tiles.clone(),
+                        sprite: TextureAtlasSprite::new(FIRE_BOLT_SPRITE),
+                        transform: Transform {
+                            translation: transform.translation,
+                            scale: Vec3::new(1.5, 1.5, 1.5),
+                            ..default()
+                        },
+                        ..default()
+                    },
+                    Projectile {
+                        velocity: input.aim().unwrap_or(facing.0) * FIRE_BOLT_SPEED,
+                        damage: stats.spell_damage(),
+                        owner: player.handle,
+                        frames_left: FIRE_BOLT_FRAMES,
+                        effect: Some((StatusKind::Burn, FIRE_BOLT_BURN_FRAMES)),
+                    },
+                    Bounding(6.0),
+                    rip.next(),
+                ));
+            }
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub fn move_projectiles(
+    mut commands: Commands,
+    mut projectiles: Query<(
+        Entity,
+        &mut Projectile,
+        &mut Transform,
+        &Bounding,
+        &SpawnOrder,
+    )>,
+    mut enemies: Query<
+        (
+            &Transform,
+            &Bounding,
+            &mut Health,
+            &mut LastHitBy,
+            &mut StatusEffects,
+            &SpawnOrder,
+        ),
+        (With<Enemy>, Without<Projectile>),
+    >,
+) {
+    let mut projectiles: Vec<_> = projectiles.iter_mut().collect();
+    projectiles.sort_by_key(|(.., order)| **order);
+
+    for (entity, mut projectile, mut transform, bounding, _) in projectiles {
+        transform.translation += projectile.velocity.extend(0.0);
+        projectile.frames_left = projectile.frames_left.saturating_sub(1);
+
+        // of overlapping enemies the first spawned is hit, the same one on every peer
+        let hit = enemies
+            .iter_mut()
+            .filter(|(et, eb, ..)| circles_touching(&transform, bounding, et, eb))
+            .min_by_key(|(.., order)| **order);
+        if let Some((_, _, mut health, mut last_hit_by, mut effects, _)) = hit {
+            health.damage(projectile.damage);
+            last_hit_by.0 = Some(projectile.owner);
+            if let Some((kind, frames)) = projectile.effect {
+                effects.apply(kind, frames, Some(projectile.owner));
+            }
+            commands.entity(entity).despawn();
+        } else if projectile.frames_left == 0 {
+            commands.entity(entity).despawn();
+        }
+    }
+}
@@ -0,0 +1,306 @@
+use bevy::prelude::*;
+use bevy_ggrs::{PlayerInputs, RollbackIdProvider};
+use bevy_turborand::RngComponent;
+
+use crate::{DataAssets, ImageAssets};
+
+use super::{
+    classes::Class,
+    collision::distance_between,
+    components::{
+        AnimationIndices, AnimationTimer, Bounding, Depth, GgrsConfig, Player, SpawnOrder,
+    },
+    effects::Flick,
+    loot::{spawn_drops, DropsLoot, LootRng, LootTables},
+    stats::{Experience, Health, Stats},
+    status::StatusEffects,
+};
+
+/**
+ * How far outside of their own bounds a player can hit.
+ */
+pub const ATTACK_REACH: f32 = 16.0;
+
+/**
+ * How far away enemies notice players.
+ */
+pub const ENEMY_SIGHT: f32 = 160.0;
+pub const ENEMY_MOVE_SPEED: f32 = 0.8;
+/**
+ * How far outside of their own bounds enemies can hit.
+ */
+pub const ENEMY_REACH: f32 = 4.0;
+pub const ENEMY_ATTACK_DAMAGE: u32 = 4;
+pub const ENEMY_ATTACK_COOLDOWN: u32 = 60;
+
+pub const SLIME_SPRITES: AnimationIndices = AnimationIndices {
+    first: 460,
+    last: 462,
+};
+
+#[derive(Debug, Component, Reflect, Default, Hash)]
+#[reflect(Component, Hash)]
+pub struct Enemy {
+    /// Experience given to the player landing the killing blow.
+    pub xp: u32,
+}
+
+/**
+ * Frames left until the entity can attack again.
+ */
+#[derive(Debug, Component, Reflect, Default, Hash)]
+#[reflect(Component, Hash)]
+pub struct AttackCooldown(pub u32);
+
+/**
+ * Handle of the last player to damage the entity.
+ */
+#[derive(Debug, Component, Reflect, Default, Hash)]
+#[reflect(Component, Hash)]
+pub struct LastHitBy(pub Option<usize>);
+
+pub fn spawn_enemy(
+    commands: &mut Commands,
+    rip: &mut RollbackIdProvider,
+    images: &ImageAssets,
+    position: Vec2,
+) {
+    commands.spawn((
+        SpriteSheetBundle {
+            texture_atlas: images.tiles.clone(),
+            sprite: TextureAtlasSprite::new(SLIME_SPRITES.first),
+            transform: Transform {
+                translation: position.extend(1.0),
+                scale: Vec3::new(2.0, 2.0, 2.0),
+                ..default()
+            },
+            ..default()
+        },
+        SLIME_SPRITES,
+        AnimationTimer::new(18),
+        Enemy { xp: 8 },
+        Health::full(30),
+        LastHitBy::default(),
+        AttackCooldown::default(),
+        StatusEffects::default(),
+        DropsLoot("enemy".into()),
+        Bounding(12.0),
+        rip.next(),
+    ));
+}
+
+/**
+ * The closest living player an enemy can see, ties going to the lowest handle so every peer picks
+ * the same one. Returns the player, where it is and how far apart their bounds are.
+ */
+fn closest_player<'a>(
+    transform: &Transform,
+    bounding: &Bounding,
+    players: impl Iterator<Item = (Entity, &'a Player, &'a Transform, &'a Bounding, &'a Health)>,
+) -> Option<(Entity, Vec3, f32)> {
+    players
+        .filter(|(.., health)| !health.is_dead())
+        .map(|(entity, player, player_transform, player_bounding, _)| {
+            let distance = distance_between(&transform.translation, &player_transform.translation);
+            let gap = distance - bounding.0 - player_bounding.0;
+            (
+                entity,
+                player.handle,
+                player_transform.translation,
+                distance,
+                gap,
+            )
+        })
+        .filter(|(.., distance, _)| *distance <= ENEMY_SIGHT)
+        .min_by(|a, b| a.3.total_cmp(&b.3).then(a.1.cmp(&b.1)))
+        .map(|(entity, _, translation, _, gap)| (entity, translation, gap))
+}
+
+/**
+ * Moves enemies towards the closest player they can see, stopping once they touch.
+ */
+#[allow(clippy::type_complexity)]
+pub fn move_enemies(
+    players: Query<(Entity, &Player, &Transform, &Bounding, &Health), Without<Enemy>>,
+    mut enemies: Query<(&mut Transform, &Bounding, &StatusEffects), With<Enemy>>,
+) {
+    for (mut transform, bounding, effects) in enemies.iter_mut() {
+        let speed = ENEMY_MOVE_SPEED * effects.move_speed_multiplier();
+        let Some((_, target, gap)) = closest_player(&transform, bounding, players.iter()) else {
+            continue;
+        };
+        let step = f32::min(speed, gap);
+        if step <= 0.0 {
+            continue;
+        }
+
+        // players are drawn on another layer, only move in the plane
+        let direction = (target - transform.translation)
+            .truncate()
+            .normalize_or_zero();
+        transform.translation += (direction * step).extend(0.0);
+    }
+}
+
+/**
+ * Enemies next to a player hit them, unless they are stunned.
+ */
+#[allow(clippy::type_complexity)]
+pub fn enemy_attacks(
+    mut commands: Commands,
+    mut players: Query<(Entity, &Player, &Transform, &Bounding, &mut Health), Without<Enemy>>,
+    mut enemies: Query<(&Transform, &Bounding, &StatusEffects, &mut AttackCooldown), With<Enemy>>,
+) {
+    for (transform, bounding, effects, mut cooldown) in enemies.iter_mut() {
+        if cooldown.0 > 0 {
+            cooldown.0 -= 1;
+            continue;
+        }
+        if effects.is_stunned() {
+            continue;
+        }
+
+        let Some((entity, _, gap)) = closest_player(transform, bounding, players.iter()) else {
+            continue;
+        };
+        if gap > ENEMY_REACH {
+            continue;
+        }
+
+        if let Ok((.., mut health)) = players.get_mut(entity) {
+            health.damage(ENEMY_ATTACK_DAMAGE);
+        }
+        cooldown.0 = ENEMY_ATTACK_COOLDOWN;
+        commands.entity(entity).insert(Flick {
+            switch_frames: 3,
+            frames_left: 18,
+        });
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub fn player_attacks(
+    mut commands: Commands,
+    inputs: Res<PlayerInputs<GgrsConfig>>,
+    mut players: Query<(
+        &Player,
+        &Class,
+        &Transform,
+        &Bounding,
+        &Stats,
+        &StatusEffects,
+        &mut AttackCooldown,
+    )>,
+    mut enemies: Query<
+        (
+            Entity,
+            &Transform,
+            &Bounding,
+            &mut Health,
+            &mut LastHitBy,
+            &mut StatusEffects,
+        ),
+        (With<Enemy>, Without<Player>),
+    >,
+) {
+    // the last hit decides who gets the experience, so players take turns by handle
+    let mut players: Vec<_> = players.iter_mut().collect();
+    players.sort_by_key(|(player, ..)| player.handle);
+
+    for (player, class, transform, bounding, stats, effects, mut cooldown) in players {
+        if cooldown.0 > 0 {
+            cooldown.0 -= 1;
+            continue;
+        }
+        if effects.is_stunned() {
+            continue;
+        }
+
+        let (input, _) = inputs[player.handle];
+        if !input.attacking() {
+            continue;
+        }
+
+        cooldown.0 = stats.attack_cooldown();
+        for (
+            entity,
+            enemy_transform,
+            enemy_bounding,
+            mut health,
+            mut last_hit_by,
+            mut enemy_effects,
+        ) in enemies.iter_mut()
+        {
+            let distance = distance_between(&transform.translation, &enemy_transform.translation);
+            if distance > bounding.0 + enemy_bounding.0 + ATTACK_REACH {
+                continue;
+            }
+
+            health.damage(stats.attack_damage());
+            last_hit_by.0 = Some(player.handle);
+            if let Some((kind, frames)) = class.0.on_hit() {
+                enemy_effects.apply(kind, frames, Some(player.handle));
+            }
+            commands.entity(entity).insert(Flick {
+                switch_frames: 3,
+                frames_left: 18,
+            });
+        }
+    }
+}
+
+/**
+ * Despawns dead enemies, rewarding the killer with experience and dropping their loot.
+ */
+#[allow(clippy::too_many_arguments)]
+pub fn kill_enemies(
+    mut commands: Commands,
+    mut rip: ResMut<RollbackIdProvider>,
+    images: Res<ImageAssets>,
+    data: Res<DataAssets>,
+    loot_tables: Res<Assets<LootTables>>,
+    depth: Res<Depth>,
+    enemies: Query<(
+        Entity,
+        &Enemy,
+        &Health,
+        &LastHitBy,
+        &Transform,
+        &DropsLoot,
+        &SpawnOrder,
+    )>,
+    mut players: Query<(&Player, &mut Experience)>,
+    mut rng: Query<&mut RngComponent, With<LootRng>>,
+) {
+    // loot is rolled and spawned in spawn order, the query could hand enemies out in any order
+    let mut enemies: Vec<_> = enemies.iter().collect();
+    enemies.sort_by_key(|(.., order)| **order);
+
+    for (entity, enemy, health, last_hit_by, transform, drops, _) in enemies {
+        if !health.is_dead() {
+            continue;
+        }
+
+        if let Some(handle) = last_hit_by.0 {
+            for (player, mut experience) in players.iter_mut() {
+                if player.handle == handle {
+                    experience.xp += enemy.xp;
+                }
+            }
+        }
+
+        if let (Some(loot), Ok(mut rng)) = (loot_tables.get(&data.loot), rng.get_single_mut()) {
+            let drops = loot.roll(&drops.0, depth.0, &mut rng);
+            spawn_drops(
+                &mut commands,
+                &mut rip,
+                &images,
+                loot,
+                &drops,
+                transform.translation,
+            );
+        }
+
+        commands.entity(entity).despawn();
+    }
+}
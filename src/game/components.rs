@@ -2,6 +2,8 @@ use bevy::prelude::*;
 use bevy_matchbox::prelude::PeerId;
 use derive_more::From;
 
+use super::input::PlayerInput;
+
 #[derive(Component)]
 pub struct ExampleGameText;
 
@@ -17,31 +19,137 @@ pub struct Vel(pub Vec2);
 #[derive(Debug, Component, From)]
 pub struct Pos(pub Vec2);
 
-#[derive(Debug, Component, From)]
+#[derive(Debug, Component, From, Reflect, Default)]
+#[reflect(Component)]
 pub struct Bounding(pub f32);
 
-#[derive(Component, Clone)]
+#[derive(Component, Clone, Reflect, Default)]
+#[reflect(Component)]
 pub struct AnimationIndices {
     pub first: usize,
     pub last: usize,
 }
 
-#[derive(Component, Deref, DerefMut)]
-pub struct AnimationTimer(pub Timer);
+/**
+ * Counts GGRS frames until the animation steps to its next sprite.
+ */
+#[derive(Component, Clone, Reflect, Default)]
+#[reflect(Component)]
+pub struct AnimationTimer {
+    pub frames_per_step: u32,
+    pub elapsed: u32,
+}
+
+impl AnimationTimer {
+    pub fn new(frames_per_step: u32) -> AnimationTimer {
+        AnimationTimer {
+            frames_per_step,
+            elapsed: 0,
+        }
+    }
 
-#[derive(Component)]
+    /**
+     * Counts one frame, returns true when it's time for the next sprite.
+     */
+    pub fn tick(&mut self) -> bool {
+        self.elapsed += 1;
+        if self.elapsed >= self.frames_per_step {
+            self.elapsed = 0;
+            return true;
+        }
+        false
+    }
+}
+
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
 pub struct Player {
     pub handle: usize,
 }
 
+/**
+ * Outfits to tell players apart, each is a row of the character atlases.
+ */
+pub const OUTFITS: usize = 4;
+pub const OUTFIT_FRAMES: usize = 4;
+
+/**
+ * Row of the character atlas the player is drawn from.
+ */
+#[derive(Component, Reflect, Default, Clone, Copy)]
+#[reflect(Component)]
+pub struct Outfit(pub usize);
+
+impl Outfit {
+    pub fn for_handle(handle: usize) -> Outfit {
+        Outfit(handle % OUTFITS)
+    }
+
+    /**
+     * Atlas index of the nth frame of this outfit.
+     */
+    pub fn sprite(&self, frame: usize) -> usize {
+        self.0 * OUTFIT_FRAMES + frame
+    }
+}
+
+/**
+ * Seeds the level generation, every peer needs the same one to build the same dungeon.
+ */
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct LevelSeed(pub u64);
+
+/**
+ * Where players are placed at the start of a level, one per player handle.
+ */
+#[derive(Resource, Default)]
+pub struct SpawnPoints(pub Vec<Vec2>);
+
 #[derive(Resource)]
 pub struct LocalPlayerHandle(pub usize);
 
+/**
+ * Every player's name, indexed by their GGRS player handle.
+ */
+#[derive(Resource, Default)]
+pub struct PlayerNames(pub Vec<String>);
+
+/**
+ * How far down the dungeon the players are, starting at 1.
+ */
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct Depth(pub u32);
+
+impl Default for Depth {
+    fn default() -> Self {
+        Depth(1)
+    }
+}
+
+/**
+ * Frames simulated since the session started, rolled back along with everything else.
+ */
+#[derive(Resource, Reflect, Default, Clone, Copy)]
+#[reflect(Resource)]
+pub struct FrameCount(pub u32);
+
+/**
+ * When a rollback entity first showed up, the same on every peer. Rollback ids keep counting up
+ * when frames are simulated again, so they can't be compared between peers.
+ */
+#[derive(Component, Reflect, Default, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[reflect(Component, Hash)]
+pub struct SpawnOrder {
+    pub frame: u32,
+    /// Position among the entities showing up on the same frame.
+    pub index: u32,
+}
+
 pub struct GgrsConfig;
 
 impl bevy_ggrs::ggrs::Config for GgrsConfig {
-    // 4-directions + fire fits easinly in a single byte
-    type Input = u8;
+    type Input = PlayerInput;
     type State = u8;
     // Matchbox' WebRtcSocket addresses are called `PeerId`s
     type Address = PeerId;
@@ -2,19 +2,31 @@ use bevy::{
     prelude::*,
     time::{Time, Timer},
 };
-use derive_more::From;
 
-#[derive(Debug, Component, Default, From)]
+/**
+ * Blinks the entity for a number of GGRS frames.
+ */
+#[derive(Debug, Component, Default, Reflect)]
+#[reflect(Component)]
 pub struct Flick {
-    pub switch_timer: Timer,
-    pub duration: Timer,
+    /// Frames between switching between hidden and visible.
+    pub switch_frames: u32,
+    pub frames_left: u32,
 }
 
 #[derive(Debug, Component)]
 pub struct TimedRemoval(pub Timer);
 
+/**
+ * Tints the sprite while a status effect lasts.
+ */
 #[derive(Debug, Component)]
-pub struct Darken(pub Timer);
+pub struct Darken {
+    pub tint: Color,
+    /// The sprite's own color, put back once the effect is gone.
+    pub original: Color,
+}
+
 #[derive(Debug, Component)]
 pub struct Shrink(pub Timer);
 
@@ -24,16 +36,14 @@ pub struct DelayedVisibility(pub Timer);
 pub fn flick_system(
     mut commands: Commands,
     mut query: Query<(Entity, &mut Visibility, &mut Flick)>,
-    time: Res<Time>,
 ) {
     for (entity, mut visibility, mut flick) in query.iter_mut() {
-        flick.duration.tick(time.delta());
-        flick.switch_timer.tick(time.delta());
+        flick.frames_left = flick.frames_left.saturating_sub(1);
 
-        if flick.duration.finished() {
+        if flick.frames_left == 0 {
             *visibility = Visibility::Inherited;
             commands.entity(entity).remove::<Flick>();
-        } else if flick.switch_timer.just_finished() {
+        } else if flick.frames_left % flick.switch_frames.max(1) == 0 {
             *visibility = match visibility.clone() {
                 Visibility::Inherited => Visibility::Hidden,
                 _ => Visibility::Inherited,
@@ -0,0 +1,69 @@
+/**
+ * 64 bit FNV-1a, for hashes peers compare with each other. Unlike `DefaultHasher` it comes out the
+ * same on every platform and Rust release. Numbers go in as little endian bytes of a fixed width,
+ * so the size of `usize` never matters.
+ */
+#[derive(Debug, Clone, Copy)]
+pub struct Fnv64(u64);
+
+const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+impl Default for Fnv64 {
+    fn default() -> Self {
+        Fnv64(OFFSET_BASIS)
+    }
+}
+
+impl Fnv64 {
+    pub fn new() -> Fnv64 {
+        Fnv64::default()
+    }
+
+    /**
+     * Hashes the bytes on their own.
+     */
+    pub fn hash_bytes(bytes: &[u8]) -> u64 {
+        let mut hasher = Fnv64::new();
+        hasher.write(bytes);
+        hasher.finish()
+    }
+
+    pub fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(PRIME);
+        }
+    }
+
+    pub fn write_u32(&mut self, value: u32) {
+        self.write(&value.to_le_bytes());
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.write(&value.to_le_bytes());
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_reference_values() {
+        assert_eq!(Fnv64::hash_bytes(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(Fnv64::hash_bytes(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(Fnv64::hash_bytes(b"foobar"), 0x8594_4171_f739_67e8);
+    }
+
+    #[test]
+    fn numbers_are_little_endian() {
+        let mut hasher = Fnv64::new();
+        hasher.write_u32(0x0403_0201);
+        assert_eq!(hasher.finish(), Fnv64::hash_bytes(&[1, 2, 3, 4]));
+    }
+}
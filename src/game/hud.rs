@@ -0,0 +1,291 @@
+use bevy::prelude::*;
+
+use crate::{FontAssets, DARK, LIGHT};
+
+use super::{
+    classes::{AbilityCooldown, Class},
+    combat::AttackCooldown,
+    components::{Depth, LocalPlayerHandle, Player, PlayerNames},
+    stats::{Experience, Health, Stats},
+};
+
+pub const HEALTH_COLOR: Color = Color::rgb(0.8, 0.2, 0.2);
+pub const EXPERIENCE_COLOR: Color = Color::rgb(0.9, 0.8, 0.3);
+pub const ABILITY_COLOR: Color = Color::rgb(0.3, 0.5, 0.9);
+
+/**
+ * Root of everything drawn over the game.
+ */
+#[derive(Component)]
+pub struct HudText;
+
+#[derive(Component)]
+pub struct FloorText;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HudStat {
+    Health,
+    Experience,
+    Ability,
+    Attack,
+}
+
+/**
+ * The filled part of a bar, as wide as the stat of the player is full.
+ */
+#[derive(Component)]
+pub struct HudBar {
+    pub handle: usize,
+    pub stat: HudStat,
+}
+
+/**
+ * The text above a bar.
+ */
+#[derive(Component)]
+pub struct HudLabel {
+    pub handle: usize,
+    pub stat: HudStat,
+}
+
+fn spawn_bar(parent: &mut ChildBuilder, handle: usize, stat: HudStat, size: Size, color: Color) {
+    parent
+        .spawn(NodeBundle {
+            style: Style {
+                size,
+                margin: UiRect::bottom(Val::Px(4.0)),
+                ..default()
+            },
+            background_color: DARK.into(),
+            ..default()
+        })
+        .with_children(|bar| {
+            bar.spawn((
+                NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Percent(0.0), Val::Percent(100.0)),
+                        ..default()
+                    },
+                    background_color: color.into(),
+                    ..default()
+                },
+                HudBar { handle, stat },
+            ));
+        });
+}
+
+fn spawn_label(parent: &mut ChildBuilder, style: &TextStyle, handle: usize, stat: HudStat) {
+    parent.spawn((
+        TextBundle::from_section("", style.clone()),
+        HudLabel { handle, stat },
+    ));
+}
+
+/**
+ * Bars for the local player in the bottom right, the floor on top and a frame for every teammate.
+ */
+pub fn setup_hud(
+    mut commands: Commands,
+    fonts: Res<FontAssets>,
+    local: Option<Res<LocalPlayerHandle>>,
+    names: Option<Res<PlayerNames>>,
+) {
+    let style = TextStyle {
+        font: fonts.visitor.clone(),
+        font_size: 16.0,
+        color: Color::WHITE,
+    };
+    let local = local.map(|local| local.0);
+    let names = names.map(|names| names.0.clone()).unwrap_or_default();
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                    position_type: PositionType::Absolute,
+                    ..default()
+                },
+                ..default()
+            },
+            HudText,
+        ))
+        .with_children(|root| {
+            root.spawn((
+                TextBundle::from_section("", style.clone()).with_style(Style {
+                    position_type: PositionType::Absolute,
+                    position: UiRect {
+                        top: Val::Px(8.0),
+                        left: Val::Percent(45.0),
+                        ..default()
+                    },
+                    ..default()
+                }),
+                FloorText,
+            ));
+
+            if let Some(handle) = local {
+                let bar = Size::new(Val::Px(160.0), Val::Px(8.0));
+                root.spawn(NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        position: UiRect {
+                            bottom: Val::Px(8.0),
+                            right: Val::Px(8.0),
+                            ..default()
+                        },
+                        flex_direction: FlexDirection::Column,
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|panel| {
+                    for (stat, color) in [
+                        (HudStat::Health, HEALTH_COLOR),
+                        (HudStat::Experience, EXPERIENCE_COLOR),
+                        (HudStat::Ability, ABILITY_COLOR),
+                        (HudStat::Attack, LIGHT),
+                    ] {
+                        spawn_label(panel, &style, handle, stat);
+                        spawn_bar(panel, handle, stat, bar, color);
+                    }
+                });
+            }
+
+            let teammate_bar = Size::new(Val::Px(100.0), Val::Px(6.0));
+            root.spawn(NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: UiRect {
+                        top: Val::Px(8.0),
+                        right: Val::Px(8.0),
+                        ..default()
+                    },
+                    flex_direction: FlexDirection::Column,
+                    ..default()
+                },
+                ..default()
+            })
+            .with_children(|frames| {
+                for handle in (0..names.len()).filter(|handle| Some(*handle) != local) {
+                    spawn_label(frames, &style, handle, HudStat::Health);
+                    spawn_bar(frames, handle, HudStat::Health, teammate_bar, HEALTH_COLOR);
+                }
+            });
+        });
+}
+
+/**
+ * The components of a player the HUD reads from.
+ */
+struct HudPlayer<'a> {
+    health: &'a Health,
+    experience: &'a Experience,
+    stats: &'a Stats,
+    class: &'a Class,
+    attack_cooldown: &'a AttackCooldown,
+    ability_cooldown: &'a AbilityCooldown,
+}
+
+/**
+ * How full the stat of the player is, and what's written above its bar.
+ */
+fn stat_display(stat: HudStat, name: &str, local: bool, player: &HudPlayer) -> (f32, String) {
+    let HudPlayer {
+        health,
+        experience,
+        stats,
+        class,
+        attack_cooldown,
+        ability_cooldown,
+    } = player;
+    let filled = |left: u32, total: u32| 1.0 - left as f32 / total.max(1) as f32;
+    match stat {
+        HudStat::Health if local => (
+            health.current as f32 / health.max.max(1) as f32,
+            format!("HP {}/{}", health.current, health.max),
+        ),
+        HudStat::Health => (
+            health.current as f32 / health.max.max(1) as f32,
+            format!("{} - {} {}", name, class.0.name(), experience.level),
+        ),
+        HudStat::Experience => (
+            experience.xp as f32 / experience.next_level_xp().max(1) as f32,
+            format!("Level {}", experience.level),
+        ),
+        HudStat::Ability => {
+            let ability = class.0.ability();
+            (
+                filled(ability_cooldown.0, ability.cooldown()),
+                ability.name().into(),
+            )
+        }
+        HudStat::Attack => (
+            filled(attack_cooldown.0, stats.attack_cooldown()),
+            "Attack".into(),
+        ),
+    }
+}
+
+pub fn update_hud(
+    depth: Res<Depth>,
+    local: Option<Res<LocalPlayerHandle>>,
+    names: Option<Res<PlayerNames>>,
+    players: Query<(
+        &Player,
+        &Health,
+        &Experience,
+        &Stats,
+        &Class,
+        &AttackCooldown,
+        &AbilityCooldown,
+    )>,
+    mut floors: Query<&mut Text, (With<FloorText>, Without<HudLabel>)>,
+    mut labels: Query<(&HudLabel, &mut Text)>,
+    mut bars: Query<(&HudBar, &mut Style)>,
+) {
+    for mut text in floors.iter_mut() {
+        let floor = format!("Floor {}", depth.0);
+        if text.sections[0].value != floor {
+            text.sections[0].value = floor;
+        }
+    }
+
+    let local = local.map(|local| local.0);
+    let display = |handle: usize, stat: HudStat| {
+        let name = names
+            .as_ref()
+            .and_then(|names| names.0.get(handle))
+            .map_or("Player", String::as_str);
+        let player = players.iter().find(|(player, ..)| player.handle == handle);
+        match player {
+            Some((_, health, experience, stats, class, attack_cooldown, ability_cooldown)) => {
+                let player = HudPlayer {
+                    health,
+                    experience,
+                    stats,
+                    class,
+                    attack_cooldown,
+                    ability_cooldown,
+                };
+                stat_display(stat, name, Some(handle) == local, &player)
+            }
+            // not spawned yet
+            None => (0.0, name.to_string()),
+        }
+    };
+
+    for (label, mut text) in labels.iter_mut() {
+        let (_, value) = display(label.handle, label.stat);
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+        }
+    }
+    for (bar, mut style) in bars.iter_mut() {
+        let (filled, _) = display(bar.handle, bar.stat);
+        let width = Val::Percent(filled.clamp(0.0, 1.0) * 100.0);
+        if style.size.width != width {
+            style.size.width = width;
+        }
+    }
+}
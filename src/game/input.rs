@@ -1,46 +1,261 @@
-use bevy::{math::vec2, prelude::*};
-use bevy_ggrs::*;
+use bevy::{prelude::*, window::PrimaryWindow};
+use bytemuck::{Pod, Zeroable};
+use serde::{Deserialize, Serialize};
+use std::f32::consts::TAU;
 
-const INPUT_DOWN: u8 = 1 << 1;
-const INPUT_UP: u8 = 1 << 0;
-const INPUT_LEFT: u8 = 1 << 2;
-const INPUT_RIGHT: u8 = 1 << 3;
-const INPUT_ATTACK: u8 = 1 << 4;
+use crate::{
+    chat::components::ChatInput,
+    controls::bindings::{Action, Bindings},
+};
 
-pub fn ggrs_input(_: In<bevy_ggrs::ggrs::PlayerHandle>, keys: Res<Input<KeyCode>>) -> u8 {
-    let mut input = 0u8;
+use super::{components::FrameCount, pause::PauseMenu, replay::ReplayPlayback};
 
-    if keys.any_pressed([KeyCode::Left, KeyCode::A]) {
-        input |= INPUT_LEFT;
+const MOVE_UP: u8 = 1 << 0;
+const MOVE_DOWN: u8 = 1 << 1;
+const MOVE_LEFT: u8 = 1 << 2;
+const MOVE_RIGHT: u8 = 1 << 3;
+
+const BUTTON_ATTACK: u8 = 1 << 0;
+const BUTTON_SECONDARY: u8 = 1 << 1;
+const BUTTON_INTERACT: u8 = 1 << 3;
+/// Set when `aim` holds a direction, the player isn't aiming without a cursor.
+const BUTTON_AIMING: u8 = 1 << 5;
+
+/**
+ * Steps of a full turn the aim angle is quantized to.
+ */
+pub const AIM_STEPS: f32 = 256.0;
+
+/**
+ * Everything a player does in a frame, packed into the bytes sent to the other peers.
+ */
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlayerInput {
+    movement: u8,
+    aim: u8,
+    buttons: u8,
+}
+
+// only bytes, so any bit pattern is a valid input and there is no padding
+unsafe impl Zeroable for PlayerInput {}
+unsafe impl Pod for PlayerInput {}
+
+impl PlayerInput {
+    /**
+     * The unnormalized direction the movement keys point in.
+     */
+    pub fn direction(&self) -> Vec2 {
+        let mut direction = Vec2::ZERO;
+
+        if self.movement & MOVE_LEFT != 0 {
+            direction.x -= 1.0;
+        }
+        if self.movement & MOVE_RIGHT != 0 {
+            direction.x += 1.0;
+        }
+        if self.movement & MOVE_UP != 0 {
+            direction.y += 1.0;
+        }
+        if self.movement & MOVE_DOWN != 0 {
+            direction.y -= 1.0;
+        }
+
+        direction
+    }
+
+    /**
+     * Unit vector towards where the player is aiming, if anywhere.
+     */
+    pub fn aim(&self) -> Option<Vec2> {
+        (self.buttons & BUTTON_AIMING != 0)
+            .then(|| Vec2::from_angle(self.aim as f32 / AIM_STEPS * TAU))
+    }
+
+    pub fn attacking(&self) -> bool {
+        self.buttons & BUTTON_ATTACK != 0
+    }
+
+    pub fn secondary(&self) -> bool {
+        self.buttons & BUTTON_SECONDARY != 0
+    }
+
+    pub fn interacting(&self) -> bool {
+        self.buttons & BUTTON_INTERACT != 0
+    }
+
+    fn set_aim(&mut self, direction: Vec2) {
+        let angle = direction.y.atan2(direction.x).rem_euclid(TAU);
+        self.aim = ((angle / TAU * AIM_STEPS).round() as u32 % AIM_STEPS as u32) as u8;
+        self.buttons |= BUTTON_AIMING;
+    }
+}
+
+/**
+ * How far a stick has to be pushed along an axis before it counts.
+ */
+pub const STICK_DEADZONE: f32 = 0.3;
+
+#[allow(clippy::too_many_arguments)]
+pub fn ggrs_input(
+    In(handle): In<bevy_ggrs::ggrs::PlayerHandle>,
+    frame: Res<FrameCount>,
+    playback: Option<Res<ReplayPlayback>>,
+    chat: Res<ChatInput>,
+    pause: Res<PauseMenu>,
+    bindings: Res<Bindings>,
+    keys: Res<Input<KeyCode>>,
+    mouse: Res<Input<MouseButton>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    gamepad_axes: Res<Axis<GamepadAxis>>,
+) -> PlayerInput {
+    // replays play back what was recorded for the frame about to be simulated
+    if let Some(playback) = playback {
+        return playback.input(frame.0 + 1, handle);
+    }
+
+    let mut input = PlayerInput::default();
+    if chat.typing || pause.open {
+        return input; // standing still beats walking into enemies while looking elsewhere
     }
-    if keys.any_pressed([KeyCode::Right, KeyCode::D]) {
-        input |= INPUT_RIGHT;
+
+    let pressed = |action| bindings.pressed(action, &keys, &mouse);
+    if pressed(Action::MoveLeft) {
+        input.movement |= MOVE_LEFT;
+    }
+    if pressed(Action::MoveRight) {
+        input.movement |= MOVE_RIGHT;
+    }
+    if pressed(Action::MoveUp) {
+        input.movement |= MOVE_UP;
+    }
+    if pressed(Action::MoveDown) {
+        input.movement |= MOVE_DOWN;
+    }
+    if pressed(Action::Attack) {
+        input.buttons |= BUTTON_ATTACK;
+    }
+    if pressed(Action::Secondary) {
+        input.buttons |= BUTTON_SECONDARY;
     }
-    if keys.any_pressed([KeyCode::Up, KeyCode::W]) {
-        input |= INPUT_UP;
+    if pressed(Action::Interact) {
+        input.buttons |= BUTTON_INTERACT;
     }
-    if keys.any_pressed([KeyCode::Down, KeyCode::S]) {
-        input |= INPUT_DOWN;
+
+    // the camera follows the local player, so the center of the window is where they stand
+    if let Ok(window) = windows.get_single() {
+        if let Some(cursor) = window.cursor_position() {
+            let center = Vec2::new(window.width(), window.height()) / 2.0;
+            let offset = cursor - center;
+            if offset != Vec2::ZERO {
+                input.set_aim(offset);
+            }
+        }
+    }
+
+    // the first connected gamepad belongs to the local player
+    if let Some(gamepad) = gamepads.iter().min_by_key(|gamepad| gamepad.id) {
+        gamepad_input(&mut input, gamepad, &gamepad_buttons, &gamepad_axes);
     }
 
     input
 }
 
-pub fn direction(input: u8) -> Vec2 {
-    let mut direction = Vec2::ZERO;
+fn stick(
+    axes: &Axis<GamepadAxis>,
+    gamepad: Gamepad,
+    x: GamepadAxisType,
+    y: GamepadAxisType,
+) -> Vec2 {
+    let x = axes.get(GamepadAxis::new(gamepad, x)).unwrap_or_default();
+    let y = axes.get(GamepadAxis::new(gamepad, y)).unwrap_or_default();
+    Vec2::new(x, y)
+}
+
+/**
+ * Adds the d-pad, left stick and buttons of the gamepad to the input, the right stick aims.
+ */
+fn gamepad_input(
+    input: &mut PlayerInput,
+    gamepad: Gamepad,
+    buttons: &Input<GamepadButton>,
+    axes: &Axis<GamepadAxis>,
+) {
+    let pressed = |button_type| buttons.pressed(GamepadButton::new(gamepad, button_type));
+
+    let movement = stick(
+        axes,
+        gamepad,
+        GamepadAxisType::LeftStickX,
+        GamepadAxisType::LeftStickY,
+    );
+    if pressed(GamepadButtonType::DPadLeft) || movement.x < -STICK_DEADZONE {
+        input.movement |= MOVE_LEFT;
+    }
+    if pressed(GamepadButtonType::DPadRight) || movement.x > STICK_DEADZONE {
+        input.movement |= MOVE_RIGHT;
+    }
+    if pressed(GamepadButtonType::DPadUp) || movement.y > STICK_DEADZONE {
+        input.movement |= MOVE_UP;
+    }
+    if pressed(GamepadButtonType::DPadDown) || movement.y < -STICK_DEADZONE {
+        input.movement |= MOVE_DOWN;
+    }
 
-    if input & INPUT_LEFT != 0 {
-        direction.x -= 1.0;
+    if pressed(GamepadButtonType::South) || pressed(GamepadButtonType::RightTrigger2) {
+        input.buttons |= BUTTON_ATTACK;
+    }
+    if pressed(GamepadButtonType::West) || pressed(GamepadButtonType::LeftTrigger2) {
+        input.buttons |= BUTTON_SECONDARY;
+    }
+    if pressed(GamepadButtonType::North) {
+        input.buttons |= BUTTON_INTERACT;
     }
-    if input & INPUT_RIGHT != 0 {
-        direction.x += 1.0;
+
+    let aim = stick(
+        axes,
+        gamepad,
+        GamepadAxisType::RightStickX,
+        GamepadAxisType::RightStickY,
+    );
+    if aim.length() > STICK_DEADZONE {
+        input.set_aim(aim);
     }
-    if input & INPUT_UP != 0 {
-        direction.y += 1.0;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aim_round_trips_within_a_step() {
+        for i in 0..64 {
+            let direction = Vec2::from_angle(i as f32 * 0.1 - 3.2);
+            let mut input = PlayerInput::default();
+            input.set_aim(direction);
+            let aim = input.aim().unwrap();
+            assert!(aim.angle_between(direction).abs() <= TAU / AIM_STEPS);
+        }
     }
-    if input & INPUT_DOWN != 0 {
-        direction.y -= 1.0;
+
+    #[test]
+    fn aim_wraps_at_a_full_turn() {
+        let mut input = PlayerInput::default();
+        input.set_aim(Vec2::from_angle(-0.0001));
+        assert_eq!(input.aim, 0);
+        input.set_aim(Vec2::from_angle(-TAU / AIM_STEPS));
+        assert_eq!(input.aim, u8::MAX);
     }
 
-    direction
+    #[test]
+    fn only_aims_with_the_aiming_bit() {
+        let mut input = PlayerInput::default();
+        assert_eq!(input.aim(), None);
+        input.set_aim(Vec2::Y);
+        assert_ne!(input.buttons & BUTTON_AIMING, 0);
+        assert!(input.aim().is_some());
+        assert!(!input.attacking());
+    }
 }
@@ -12,6 +12,8 @@ use bevy_turborand::{DelegatedRng, GlobalRng, RngComponent};
 use std::ops::Add;
 use std::ops::Range;
 
+use super::fnv::Fnv64;
+
 // rad 1 er 0 .. 22
 // rad 2 er 23 .. 45
 // rad 3 er 46 .. 68
@@ -85,9 +87,58 @@ pub enum CaveAtlasIndices {
     CaveFloor5_r = 101,
 }
 
+impl CaveAtlasIndices {
+    pub const ALL: [CaveAtlasIndices; 28] = [
+        CaveAtlasIndices::Wall1RightBottomLeft,
+        CaveAtlasIndices::Wall1TopBottomLeft,
+        CaveAtlasIndices::Wall1RightBottom,
+        CaveAtlasIndices::Wall1RightLeft,
+        CaveAtlasIndices::Wall1BottomLeft,
+        CaveAtlasIndices::Wall1TopRightBottomLeft,
+        CaveAtlasIndices::Wall1TopRightBottom,
+        CaveAtlasIndices::Wall1TopRightLeft,
+        CaveAtlasIndices::Wall1TopRight,
+        CaveAtlasIndices::Wall1Left,
+        CaveAtlasIndices::Wall1TopBottom,
+        CaveAtlasIndices::Wall1Top,
+        CaveAtlasIndices::Wall1Bottom,
+        CaveAtlasIndices::Wall1Right,
+        CaveAtlasIndices::Wall1TopLeft,
+        CaveAtlasIndices::Wall1Gate,
+        CaveAtlasIndices::Wall1Rubble1,
+        CaveAtlasIndices::Wall1Rubble2,
+        CaveAtlasIndices::CaveFloor1_d,
+        CaveAtlasIndices::CaveFloor1_r,
+        CaveAtlasIndices::CaveFloor2_r,
+        CaveAtlasIndices::CaveFloor2_d,
+        CaveAtlasIndices::CaveFloor3_d,
+        CaveAtlasIndices::CaveFloor3_r,
+        CaveAtlasIndices::CaveFloor4_r,
+        CaveAtlasIndices::CaveFloor4_d,
+        CaveAtlasIndices::CaveFloor5_d,
+        CaveAtlasIndices::CaveFloor5_r,
+    ];
+}
+
 const ROOM_GENERATION_ATTEMPTS: i32 = 50;
 const CORRIDOR_MAX_LENGTH: usize = 20;
 pub static ROOM_SIZES: [TilePos; 2] = [TilePos { x: 5, y: 5 }, TilePos { x: 5, y: 7 }];
+
+/**
+ * Feeds everything the level generator depends on besides the seed into the hasher.
+ */
+pub fn hash_generation_params(hasher: &mut Fnv64) {
+    hasher.write_u32(ROOM_GENERATION_ATTEMPTS as u32);
+    hasher.write_u32(CORRIDOR_MAX_LENGTH as u32);
+    for size in ROOM_SIZES.iter() {
+        hasher.write_u32(size.x);
+        hasher.write_u32(size.y);
+    }
+    for index in CaveAtlasIndices::ALL {
+        hasher.write_u32(index as u32);
+    }
+}
+
 pub struct Room {
     pub pos: TilePos,
     pub size: TilePos,
@@ -104,6 +155,21 @@ impl Room {
 
         left < right && top < bottom
     }
+    pub fn center(&self) -> TilePos {
+        TilePos::new(self.pos.x + self.size.x / 2, self.pos.y + self.size.y / 2)
+    }
+    /**
+     * The tiles diagonally around the center, where up to four players start.
+     */
+    pub fn spawn_points(&self) -> [TilePos; 4] {
+        let center = self.center();
+        [
+            TilePos::new(center.x - 1, center.y + 1),
+            TilePos::new(center.x + 1, center.y + 1),
+            TilePos::new(center.x - 1, center.y - 1),
+            TilePos::new(center.x + 1, center.y - 1),
+        ]
+    }
 }
 
 pub struct Map {
@@ -419,7 +485,7 @@ pub fn cave(
     tilemap_entity: &Entity,
     map_size: &TilemapSize,
     tile_storage: &mut TileStorage,
-) {
+) -> Vec<Room> {
     let mut map = Map {
         size: map_size.clone(),
         tiles: vec![CoarseTileType::Dirt; map_size.x as usize * map_size.y as usize],
@@ -653,4 +719,6 @@ pub fn cave(
         }
         // }
     });
+
+    rooms
 }
@@ -0,0 +1,415 @@
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::{BoxedFuture, HashMap},
+};
+use bevy_ggrs::{PlayerInputs, RollbackIdProvider};
+use bevy_turborand::{DelegatedRng, RngComponent};
+use serde::Deserialize;
+use std::ops::RangeInclusive;
+
+use crate::{DataAssets, ImageAssets};
+
+use super::{
+    collision::circles_touching,
+    components::{Bounding, Depth, GgrsConfig, LevelSeed, Player, SpawnOrder},
+    fnv::Fnv64,
+};
+
+/**
+ * Mixed into the level seed, so the loot rng doesn't draw the numbers the level was built from.
+ */
+pub const LOOT_SALT: u64 = 0x5eed_1007;
+
+pub const CHEST_CLOSED_SPRITE: usize = 200;
+pub const CHEST_OPEN_SPRITE: usize = 201;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize, Reflect, FromReflect)]
+pub enum Rarity {
+    #[default]
+    Common,
+    Uncommon,
+    Rare,
+    Epic,
+    Legendary,
+}
+
+impl Rarity {
+    /**
+     * Multiplier applied to the weight of loot entries of this rarity.
+     */
+    pub fn weight(&self) -> u32 {
+        match self {
+            Rarity::Common => 16,
+            Rarity::Uncommon => 8,
+            Rarity::Rare => 4,
+            Rarity::Epic => 2,
+            Rarity::Legendary => 1,
+        }
+    }
+
+    pub fn color(&self) -> Color {
+        match self {
+            Rarity::Common => Color::WHITE,
+            Rarity::Uncommon => Color::rgb(0.6, 1.0, 0.6),
+            Rarity::Rare => Color::rgb(0.6, 0.7, 1.0),
+            Rarity::Epic => Color::rgb(0.9, 0.6, 1.0),
+            Rarity::Legendary => Color::rgb(1.0, 0.8, 0.4),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ItemDef {
+    pub name: String,
+    pub sprite: usize,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LootEntry {
+    pub item: String,
+    pub rarity: Rarity,
+    pub weight: u32,
+    pub depths: (u32, u32),
+}
+
+impl LootEntry {
+    pub fn depths(&self) -> RangeInclusive<u32> {
+        self.depths.0..=self.depths.1
+    }
+
+    /**
+     * Weight of the entry with its rarity applied, capped instead of overflowing.
+     */
+    pub fn chance(&self) -> u32 {
+        self.weight.saturating_mul(self.rarity.weight())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LootTable {
+    /// How many times the pool is rolled.
+    pub rolls: u32,
+    /// Weight of rolling nothing at all.
+    pub nothing: u32,
+    pub entries: Vec<LootEntry>,
+}
+
+impl LootTable {
+    /**
+     * Rolls the table for the given depth. Entries are iterated in the order they
+     * are defined, so the same rng state always gives the same drops.
+     */
+    pub fn roll(&self, depth: u32, rng: &mut RngComponent) -> Vec<&LootEntry> {
+        let pool = self
+            .entries
+            .iter()
+            .filter(|e| e.depths().contains(&depth))
+            .collect::<Vec<&LootEntry>>();
+        let total = pool
+            .iter()
+            .fold(self.nothing, |total, e| total.saturating_add(e.chance()));
+
+        let mut drops = Vec::new();
+        if total == 0 {
+            return drops;
+        }
+
+        for _ in 0..self.rolls {
+            let mut pick = rng.u32(0..total);
+            for entry in pool.iter() {
+                let weight = entry.chance();
+                if pick < weight {
+                    drops.push(*entry);
+                    break;
+                }
+                pick -= weight;
+            }
+        }
+
+        drops
+    }
+}
+
+#[derive(Debug, Deserialize, TypeUuid)]
+#[uuid = "5c7a2b8e-3f4d-4b8a-9a43-1f0f5d0e7c21"]
+pub struct LootTables {
+    pub items: HashMap<String, ItemDef>,
+    pub tables: HashMap<String, LootTable>,
+    /// Hash of the file the tables were read from, peers must have the same one.
+    #[serde(skip)]
+    pub source_hash: u64,
+}
+
+impl LootTables {
+    pub fn roll(&self, table: &str, depth: u32, rng: &mut RngComponent) -> Vec<&LootEntry> {
+        match self.tables.get(table) {
+            Some(table) => table.roll(depth, rng),
+            None => {
+                error!("Unknown loot table {:?}", table);
+                Vec::new()
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct LootTablesLoader;
+
+impl AssetLoader for LootTablesLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let mut tables = ron::de::from_bytes::<LootTables>(bytes)?;
+            tables.source_hash = Fnv64::hash_bytes(bytes);
+            load_context.set_default_asset(LoadedAsset::new(tables));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["loot.ron"]
+    }
+}
+
+/**
+ * Marks the entity holding the rollback registered rng used for every loot roll.
+ */
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct LootRng;
+
+/**
+ * Name of the loot table rolled when the entity dies or is opened.
+ */
+#[derive(Debug, Component, Clone, Reflect, Default)]
+#[reflect(Component)]
+pub struct DropsLoot(pub String);
+
+#[derive(Debug, Component, Default, Reflect, Hash)]
+#[reflect(Component, Hash)]
+pub struct Chest {
+    pub opened: bool,
+}
+
+#[derive(Debug, Component, Default, Reflect, Hash)]
+#[reflect(Component, Hash)]
+pub struct Item {
+    pub id: String,
+    pub rarity: Rarity,
+}
+
+pub fn spawn_loot_rng(
+    mut commands: Commands,
+    mut rip: ResMut<RollbackIdProvider>,
+    seed: Res<LevelSeed>,
+) {
+    commands.spawn((
+        LootRng,
+        RngComponent::with_seed(seed.0 ^ LOOT_SALT),
+        rip.next(),
+    ));
+}
+
+pub fn spawn_chest(
+    commands: &mut Commands,
+    rip: &mut RollbackIdProvider,
+    images: &ImageAssets,
+    position: Vec2,
+) {
+    commands.spawn((
+        SpriteSheetBundle {
+            texture_atlas: images.tiles.clone(),
+            sprite: TextureAtlasSprite::new(CHEST_CLOSED_SPRITE),
+            transform: Transform {
+                translation: position.extend(1.0),
+                scale: Vec3::new(2.0, 2.0, 2.0),
+                ..default()
+            },
+            ..default()
+        },
+        Chest::default(),
+        DropsLoot("chest".into()),
+        Bounding(16.0),
+        rip.next(),
+    ));
+}
+
+/**
+ * Spawns the rolled items scattered around `origin`.
+ */
+pub fn spawn_drops(
+    commands: &mut Commands,
+    rip: &mut RollbackIdProvider,
+    images: &ImageAssets,
+    loot: &LootTables,
+    drops: &[&LootEntry],
+    origin: Vec3,
+) {
+    for (i, entry) in drops.iter().enumerate() {
+        let Some(def) = loot.items.get(&entry.item) else {
+            error!("Unknown item {:?} in loot table", entry.item);
+            continue;
+        };
+
+        // fan the drops out so they don't stack on top of each other
+        let offset = Vec2::from_angle(i as f32 * 2.4) * 12.0;
+        commands.spawn((
+            SpriteSheetBundle {
+                texture_atlas: images.tiles.clone(),
+                sprite: TextureAtlasSprite {
+                    index: def.sprite,
+                    color: entry.rarity.color(),
+                    ..default()
+                },
+                transform: Transform {
+                    translation: origin + offset.extend(0.0),
+                    scale: Vec3::new(1.5, 1.5, 1.5),
+                    ..default()
+                },
+                ..default()
+            },
+            Item {
+                id: entry.item.clone(),
+                rarity: entry.rarity,
+            },
+            rip.next(),
+        ));
+    }
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn open_chests(
+    mut commands: Commands,
+    mut rip: ResMut<RollbackIdProvider>,
+    images: Res<ImageAssets>,
+    data: Res<DataAssets>,
+    loot_tables: Res<Assets<LootTables>>,
+    depth: Res<Depth>,
+    inputs: Res<PlayerInputs<GgrsConfig>>,
+    players: Query<(&Player, &Transform, &Bounding)>,
+    mut chests: Query<
+        (
+            &mut Chest,
+            &Transform,
+            &Bounding,
+            &DropsLoot,
+            &mut TextureAtlasSprite,
+            &SpawnOrder,
+        ),
+        Without<Player>,
+    >,
+    mut rng: Query<&mut RngComponent, With<LootRng>>,
+) {
+    let Some(loot) = loot_tables.get(&data.loot) else {
+        return;
+    };
+    let Ok(mut rng) = rng.get_single_mut() else {
+        return;
+    };
+
+    // chests roll the rng in spawn order, the query could hand them out in any order
+    let mut chests: Vec<_> = chests.iter_mut().collect();
+    chests.sort_by_key(|(.., order)| **order);
+
+    for (mut chest, transform, bounding, drops, mut sprite, _) in chests {
+        if chest.opened {
+            continue;
+        }
+
+        let opened = players.iter().any(|(player, pt, pb)| {
+            inputs[player.handle].0.interacting() && circles_touching(pt, pb, transform, bounding)
+        });
+        if !opened {
+            continue;
+        }
+
+        chest.opened = true;
+        sprite.index = CHEST_OPEN_SPRITE;
+        let drops = loot.roll(&drops.0, depth.0, &mut rng);
+        spawn_drops(
+            &mut commands,
+            &mut rip,
+            &images,
+            loot,
+            &drops,
+            transform.translation,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(item: &str, weight: u32, depths: (u32, u32)) -> LootEntry {
+        LootEntry {
+            item: item.into(),
+            rarity: Rarity::Legendary,
+            weight,
+            depths,
+        }
+    }
+
+    fn table(rolls: u32, nothing: u32, entries: Vec<LootEntry>) -> LootTable {
+        LootTable {
+            rolls,
+            nothing,
+            entries,
+        }
+    }
+
+    fn items(drops: Vec<&LootEntry>) -> Vec<&str> {
+        drops.iter().map(|e| e.item.as_str()).collect()
+    }
+
+    #[test]
+    fn same_seed_same_drops() {
+        let table = table(
+            8,
+            1,
+            vec![
+                entry("a", 1, (0, 9)),
+                entry("b", 1, (0, 9)),
+                entry("c", 1, (0, 9)),
+            ],
+        );
+        let first = items(table.roll(1, &mut RngComponent::with_seed(7)));
+        let second = items(table.roll(1, &mut RngComponent::with_seed(7)));
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn only_rolls_entries_for_the_depth() {
+        let table = table(
+            16,
+            0,
+            vec![entry("shallow", 1, (0, 1)), entry("deep", 1, (2, 9))],
+        );
+        let drops = items(table.roll(3, &mut RngComponent::with_seed(1)));
+        assert_eq!(drops, vec!["deep"; 16]);
+    }
+
+    #[test]
+    fn nothing_to_roll() {
+        let empty = table(4, 0, vec![entry("deep", 1, (5, 9))]);
+        assert!(empty.roll(0, &mut RngComponent::with_seed(1)).is_empty());
+        let nothing = table(4, 1, vec![entry("deep", 1, (5, 9))]);
+        assert!(nothing.roll(0, &mut RngComponent::with_seed(1)).is_empty());
+    }
+
+    #[test]
+    fn huge_weights_saturate() {
+        let table = table(
+            4,
+            0,
+            vec![entry("a", u32::MAX, (0, 0)), entry("b", u32::MAX, (0, 0))],
+        );
+        assert_eq!(table.entries[0].chance(), u32::MAX);
+        assert_eq!(table.roll(0, &mut RngComponent::with_seed(1)).len(), 4);
+    }
+}
@@ -1,21 +1,64 @@
 use self::{
-    components::{GgrsConfig, PhysicsSet},
-    effects::flick_system,
+    checksum::{
+        count_frames, exchange_checksums, order_spawns, record_checksum, ChecksumHistory,
+        StateChecksum,
+    },
+    classes::{
+        move_projectiles, use_abilities, AbilityCooldown, Class, Dashing, Facing, Projectile,
+    },
+    combat::{
+        enemy_attacks, kill_enemies, move_enemies, player_attacks, AttackCooldown, Enemy, LastHitBy,
+    },
+    components::{
+        AnimationIndices, AnimationTimer, Bounding, Depth, FrameCount, GgrsConfig, Outfit,
+        PhysicsSet, Player, SpawnOrder,
+    },
+    effects::{flick_system, Flick},
+    hud::{setup_hud, update_hud},
     input::ggrs_input,
-    systems::{
-        animate_sprite, camera_follow, move_players, setup_level, spawn_player, teardown,
-        wait_for_players,
+    loot::{
+        open_chests, spawn_loot_rng, Chest, DropsLoot, Item, LootRng, LootTables, LootTablesLoader,
+    },
+    net_stats::{
+        count_rollbacks, setup_net_stats, toggle_net_stats, update_net_stats, NetStatsOverlay,
+        RollbackCounter,
     },
+    network::{cleanup_session, handle_ggrs_events, setup_network_status, update_network_status},
+    pause::{pause_local_session, pause_menu_controls, update_pause_menu, PauseMenu},
+    protocol::{receive_messages, ReceivedMessage},
+    replay::{
+        fast_forward, keep_replay_inputs, record_inputs, replay_controls, save_replay,
+        setup_replay, track_confirmed_frame, update_replay_text, ReplayPlayback, ReplayRecorder,
+    },
+    spectator::{spectator_camera, spectator_controls, SpectatorCamera},
+    stats::{level_up, Experience, Health, Stats},
+    status::{hazard_effects, step_status_effects, tint_status_effects, StatusEffects},
+    systems::{animate_sprite, camera_follow, move_players, setup_level, spawn_player, teardown},
 };
 use crate::GameState;
-use bevy::prelude::*;
+use bevy::{prelude::*, render::view::ComputedVisibility};
 use bevy_ggrs::{GGRSPlugin, GGRSSchedule};
+use bevy_turborand::RngComponent;
 
+mod checksum;
+pub mod classes;
 mod collision;
-mod components;
+mod combat;
+pub mod components;
 mod effects;
+pub mod fnv;
+mod hud;
 mod input;
 mod levels;
+pub mod loot;
+mod net_stats;
+mod network;
+pub mod pause;
+pub mod protocol;
+pub mod replay;
+pub mod spectator;
+mod stats;
+mod status;
 mod systems;
 
 pub struct GamePlugin;
@@ -23,24 +66,162 @@ impl Plugin for GamePlugin {
     fn build(&self, app: &mut App) {
         GGRSPlugin::<GgrsConfig>::new()
             .with_input_system(ggrs_input)
+            // every component of a rollback entity needs to be registered for despawned entities
+            // to come back whole when rolling back
             .register_rollback_component::<Transform>()
+            .register_rollback_component::<GlobalTransform>()
+            .register_rollback_component::<TextureAtlasSprite>()
+            .register_rollback_component::<Handle<TextureAtlas>>()
+            .register_rollback_component::<Visibility>()
+            .register_rollback_component::<ComputedVisibility>()
+            .register_rollback_component::<Player>()
+            .register_rollback_component::<Name>()
+            .register_rollback_component::<Bounding>()
+            .register_rollback_component::<AnimationIndices>()
+            .register_rollback_component::<AnimationTimer>()
+            .register_rollback_component::<Flick>()
+            .register_rollback_component::<Outfit>()
+            .register_rollback_component::<SpawnOrder>()
+            .register_rollback_component::<LootRng>()
+            .register_rollback_component::<DropsLoot>()
+            .register_rollback_component::<RngComponent>()
+            .register_rollback_component::<Chest>()
+            .register_rollback_component::<Item>()
+            .register_rollback_component::<Stats>()
+            .register_rollback_component::<Health>()
+            .register_rollback_component::<Experience>()
+            .register_rollback_component::<Enemy>()
+            .register_rollback_component::<AttackCooldown>()
+            .register_rollback_component::<LastHitBy>()
+            .register_rollback_component::<Class>()
+            .register_rollback_component::<AbilityCooldown>()
+            .register_rollback_component::<Facing>()
+            .register_rollback_component::<Dashing>()
+            .register_rollback_component::<Projectile>()
+            .register_rollback_component::<StatusEffects>()
+            .register_rollback_resource::<Depth>()
+            .register_rollback_resource::<FrameCount>()
+            .register_rollback_resource::<StateChecksum>()
             .build(app);
 
-        app.add_systems((
-            setup_level.in_schedule(OnEnter(GameState::InGame)),
-            spawn_player.in_schedule(OnEnter(GameState::InGame)),
+        app.add_asset::<LootTables>()
+            .init_asset_loader::<LootTablesLoader>()
+            .init_resource::<Depth>()
+            .init_resource::<FrameCount>()
+            .init_resource::<ChecksumHistory>()
+            .init_resource::<StateChecksum>()
+            .init_resource::<NetStatsOverlay>()
+            .init_resource::<RollbackCounter>()
+            .init_resource::<ReplayRecorder>()
+            .init_resource::<PauseMenu>()
+            .add_event::<ReceivedMessage>();
+
+        // players spawn at the spawn points of the level
+        app.add_systems(
+            (setup_level, apply_system_buffers, spawn_player)
+                .chain()
+                .in_schedule(OnEnter(GameState::InGame)),
+        )
+        .add_systems((
+            spawn_loot_rng.in_schedule(OnEnter(GameState::InGame)),
+            setup_network_status.in_schedule(OnEnter(GameState::InGame)),
+            setup_net_stats.in_schedule(OnEnter(GameState::InGame)),
+            setup_hud.in_schedule(OnEnter(GameState::InGame)),
+            setup_replay
+                .run_if(resource_exists::<ReplayPlayback>())
+                .in_schedule(OnEnter(GameState::InGame)),
         ))
         .add_systems((
-            wait_for_players.run_if(in_state(GameState::Matchmaking)),
             camera_follow.run_if(in_state(GameState::InGame)),
-            animate_sprite.run_if(in_state(GameState::InGame)),
+            tint_status_effects.run_if(in_state(GameState::InGame)),
         ))
         .add_systems(
-            (move_players, flick_system)
+            (
+                spectator_controls.run_if(resource_exists::<SpectatorCamera>()),
+                spectator_camera.run_if(resource_exists::<SpectatorCamera>()),
+            )
+                .chain()
+                .in_set(OnUpdate(GameState::InGame)),
+        )
+        .add_systems(
+            (handle_ggrs_events, update_network_status)
+                .chain()
+                .in_set(OnUpdate(GameState::InGame)),
+        )
+        .add_systems(
+            (toggle_net_stats, update_net_stats)
+                .chain()
+                .in_set(OnUpdate(GameState::InGame)),
+        )
+        .add_systems(
+            (
+                replay_controls.run_if(resource_exists::<ReplayPlayback>()),
+                fast_forward.run_if(resource_exists::<ReplayPlayback>()),
+                update_replay_text.run_if(resource_exists::<ReplayPlayback>()),
+            )
+                .chain()
+                .in_set(OnUpdate(GameState::InGame)),
+        )
+        .add_systems(
+            (pause_menu_controls, pause_local_session, update_pause_menu)
+                .chain()
+                .in_set(OnUpdate(GameState::InGame)),
+        )
+        .add_system(track_confirmed_frame.in_set(OnUpdate(GameState::InGame)))
+        .add_system(update_hud.in_set(OnUpdate(GameState::InGame)))
+        .add_systems(
+            (receive_messages, exchange_checksums)
+                .chain()
+                .in_set(OnUpdate(GameState::InGame)),
+        )
+        .add_systems(
+            (
+                count_frames,
+                order_spawns,
+                apply_system_buffers,
+                count_rollbacks,
+                move_players,
+                move_projectiles,
+                hazard_effects,
+                open_chests,
+                player_attacks,
+                use_abilities,
+                move_enemies,
+                enemy_attacks,
+            )
                 .chain()
                 .in_schedule(GGRSSchedule),
         )
+        .add_systems(
+            (
+                step_status_effects,
+                kill_enemies,
+                level_up,
+                flick_system,
+                animate_sprite,
+                record_checksum,
+            )
+                .chain()
+                .after(enemy_attacks)
+                .in_schedule(GGRSSchedule),
+        )
+        .add_systems(
+            (
+                record_inputs,
+                keep_replay_inputs.run_if(resource_exists::<ReplayPlayback>()),
+            )
+                .chain()
+                .after(record_checksum)
+                .in_schedule(GGRSSchedule),
+        )
         .configure_set(PhysicsSet::Movement.before(PhysicsSet::CollisionDetection))
-        .add_system(teardown.in_schedule(OnExit(GameState::InGame)));
+        .add_systems(
+            (
+                teardown,
+                save_replay.before(cleanup_session),
+                cleanup_session,
+            )
+                .in_schedule(OnExit(GameState::InGame)),
+        );
     }
 }
@@ -0,0 +1,165 @@
+use bevy::prelude::*;
+use bevy_ggrs::Session;
+
+use crate::FontAssets;
+
+use super::components::{FrameCount, GgrsConfig, PlayerNames};
+
+/**
+ * Seconds between refreshes of the overlay, rollbacks are counted over the same span.
+ */
+pub const NET_STATS_INTERVAL_SECONDS: f32 = 1.0;
+
+#[derive(Component)]
+pub struct NetStatsText;
+
+/**
+ * Rollbacks since the overlay was last refreshed, counted from the GGRS schedule.
+ */
+#[derive(Resource, Default)]
+pub struct RollbackCounter {
+    last_frame: u32,
+    latest_frame: u32,
+    rollbacks: u32,
+    resimulated: u32,
+}
+
+/**
+ * Whether the network stats are shown, F3 toggles them.
+ */
+#[derive(Resource)]
+pub struct NetStatsOverlay {
+    pub visible: bool,
+    timer: Timer,
+}
+
+impl Default for NetStatsOverlay {
+    fn default() -> Self {
+        NetStatsOverlay {
+            visible: false,
+            timer: Timer::from_seconds(NET_STATS_INTERVAL_SECONDS, TimerMode::Repeating),
+        }
+    }
+}
+
+/**
+ * Frames simulated at or before a frame simulated earlier are being rolled back to.
+ */
+pub fn count_rollbacks(frame: Res<FrameCount>, mut counter: ResMut<RollbackCounter>) {
+    if frame.0 <= counter.latest_frame {
+        counter.resimulated += 1;
+        if frame.0 <= counter.last_frame {
+            counter.rollbacks += 1;
+        }
+    } else {
+        counter.latest_frame = frame.0;
+    }
+    counter.last_frame = frame.0;
+}
+
+pub fn setup_net_stats(
+    mut commands: Commands,
+    fonts: Res<FontAssets>,
+    overlay: Res<NetStatsOverlay>,
+) {
+    commands.spawn((
+        TextBundle::from_sections([TextSection::new(
+            "",
+            TextStyle {
+                font: fonts.visitor.clone(),
+                font_size: 16.0,
+                color: Color::WHITE,
+            },
+        )])
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                top: Val::Px(8.0),
+                left: Val::Px(8.0),
+                ..default()
+            },
+            ..default()
+        }),
+        if overlay.visible {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        },
+        NetStatsText,
+    ));
+}
+
+pub fn toggle_net_stats(
+    keyboard: Res<Input<KeyCode>>,
+    mut overlay: ResMut<NetStatsOverlay>,
+    mut texts: Query<&mut Visibility, With<NetStatsText>>,
+) {
+    if keyboard.just_pressed(KeyCode::F3) {
+        overlay.visible = !overlay.visible;
+    }
+
+    // the pause menu can toggle them too
+    let shown = if overlay.visible {
+        Visibility::Inherited
+    } else {
+        Visibility::Hidden
+    };
+    for mut visibility in texts.iter_mut() {
+        if *visibility != shown {
+            *visibility = shown;
+        }
+    }
+}
+
+/**
+ * Refreshes the overlay with the ping, send queue and frame advantage of every remote player.
+ */
+pub fn update_net_stats(
+    time: Res<Time>,
+    session: Option<Res<Session<GgrsConfig>>>,
+    names: Option<Res<PlayerNames>>,
+    mut overlay: ResMut<NetStatsOverlay>,
+    mut counter: ResMut<RollbackCounter>,
+    mut texts: Query<&mut Text, With<NetStatsText>>,
+) {
+    if !overlay.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    let mut lines = Vec::new();
+    match session.as_deref() {
+        Some(Session::P2PSession(session)) => {
+            for handle in session.remote_player_handles() {
+                let name = names
+                    .as_ref()
+                    .and_then(|names| names.0.get(handle).cloned())
+                    .unwrap_or_else(|| format!("Player {}", handle + 1));
+                lines.push(match session.network_stats(handle) {
+                    Ok(stats) => format!(
+                        "{name}: ping {}ms, queue {}, behind {}/{}, {} kbps",
+                        stats.ping,
+                        stats.send_queue_len,
+                        stats.local_frames_behind,
+                        stats.remote_frames_behind,
+                        stats.kbps_sent
+                    ),
+                    Err(_) => format!("{name}: no stats yet"),
+                });
+            }
+        }
+        _ => lines.push("Not in a peer to peer session".into()),
+    }
+
+    let seconds = overlay.timer.duration().as_secs_f32();
+    lines.push(format!(
+        "Rollbacks: {:.1}/s, {:.1} frames/s resimulated",
+        counter.rollbacks as f32 / seconds,
+        counter.resimulated as f32 / seconds
+    ));
+    counter.rollbacks = 0;
+    counter.resimulated = 0;
+
+    for mut text in texts.iter_mut() {
+        text.sections[0].value = lines.join("\n");
+    }
+}
@@ -0,0 +1,224 @@
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::{TilePos, TileStorage};
+use bevy_ggrs::{
+    ggrs::{GGRSEvent, SessionState},
+    Rollback, Session,
+};
+use bevy_matchbox::prelude::{CloseSocketExt, MultipleChannels};
+
+use crate::{FontAssets, GameState};
+
+use super::{
+    checksum::{ChecksumHistory, StateChecksum},
+    classes::PlayerClasses,
+    components::{Depth, FrameCount, GgrsConfig, LevelSeed, LocalPlayerHandle, PlayerNames},
+    hud::HudText,
+    net_stats::{NetStatsText, RollbackCounter},
+    pause::{PauseMenu, PauseMenuText},
+    replay::{ReplayPlayback, ReplayRecorder, ReplayText},
+    spectator::SpectatorCamera,
+    status::Hazard,
+};
+
+/**
+ * Seconds the "returning to menu" message stays up after the match is lost.
+ */
+pub const RETURN_TO_MENU_SECONDS: f32 = 5.0;
+/**
+ * Seconds to wait for every peer to synchronize before giving up on the match.
+ */
+pub const SYNCHRONIZE_TIMEOUT_SECONDS: f32 = 15.0;
+
+#[derive(Component)]
+pub struct NetworkStatusText;
+
+/**
+ * What the session is going through, shown on screen while it isn't running smoothly.
+ */
+#[derive(Resource, Default)]
+pub struct NetworkStatus {
+    pub message: Option<String>,
+    /// Counting down to leaving the match.
+    pub leave_timer: Option<Timer>,
+    /// Running until every peer is synchronized.
+    pub synchronize_timer: Option<Timer>,
+}
+
+impl NetworkStatus {
+    fn leave(&mut self, message: String) {
+        self.message = Some(format!("{message}\nReturning to menu..."));
+        self.leave_timer = Some(Timer::from_seconds(RETURN_TO_MENU_SECONDS, TimerMode::Once));
+    }
+}
+
+pub fn setup_network_status(mut commands: Commands, fonts: Res<FontAssets>) {
+    commands.insert_resource(NetworkStatus {
+        synchronize_timer: Some(Timer::from_seconds(
+            SYNCHRONIZE_TIMEOUT_SECONDS,
+            TimerMode::Once,
+        )),
+        ..default()
+    });
+
+    commands.spawn((
+        TextBundle::from_sections([TextSection::new(
+            "",
+            TextStyle {
+                font: fonts.visitor.clone(),
+                font_size: 24.0,
+                color: Color::WHITE,
+            },
+        )])
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                top: Val::Percent(20.0),
+                ..default()
+            },
+            margin: UiRect {
+                left: Val::Auto,
+                right: Val::Auto,
+                ..default()
+            },
+            ..default()
+        }),
+        NetworkStatusText,
+    ));
+}
+
+pub fn handle_ggrs_events(
+    mut session: Option<ResMut<Session<GgrsConfig>>>,
+    mut status: ResMut<NetworkStatus>,
+) {
+    let events: Vec<_> = match session.as_deref_mut() {
+        Some(Session::P2PSession(session)) => session.events().collect(),
+        Some(Session::SpectatorSession(session)) => session.events().collect(),
+        _ => return,
+    };
+
+    for event in events {
+        match event {
+            GGRSEvent::Synchronizing { addr, total, count } => {
+                info!("Synchronizing with {:?} {}/{}", addr, count, total);
+                status.message = Some(format!("Synchronizing {count}/{total}"));
+            }
+            GGRSEvent::Synchronized { addr } => {
+                info!("Synchronized with {:?}", addr);
+                status.message = None;
+            }
+            GGRSEvent::NetworkInterrupted {
+                addr,
+                disconnect_timeout,
+            } => {
+                warn!("Connection to {:?} interrupted", addr);
+                status.message = Some(format!(
+                    "Connection interrupted\nDisconnecting in {:.1}s",
+                    disconnect_timeout as f32 / 1000.0
+                ));
+            }
+            GGRSEvent::NetworkResumed { addr } => {
+                info!("Connection to {:?} resumed", addr);
+                status.message = None;
+            }
+            GGRSEvent::Disconnected { addr } => {
+                warn!("{:?} disconnected", addr);
+                status.leave("A player disconnected".into());
+            }
+            GGRSEvent::DesyncDetected { frame, addr, .. } => {
+                error!("Desync with {:?} detected at frame {}", addr, frame);
+            }
+            GGRSEvent::WaitRecommendation { .. } => {}
+        }
+    }
+}
+
+/**
+ * Counts down the status timers, leaving the match when they run out.
+ */
+pub fn update_network_status(
+    time: Res<Time>,
+    session: Option<Res<Session<GgrsConfig>>>,
+    mut status: ResMut<NetworkStatus>,
+    mut texts: Query<&mut Text, With<NetworkStatusText>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let synchronized = match session.as_deref() {
+        Some(Session::P2PSession(session)) => session.current_state() == SessionState::Running,
+        Some(Session::SpectatorSession(session)) => {
+            session.current_state() == SessionState::Running
+        }
+        _ => true,
+    };
+    if synchronized {
+        status.synchronize_timer = None;
+    }
+
+    let timed_out = status
+        .synchronize_timer
+        .as_mut()
+        .is_some_and(|timer| timer.tick(time.delta()).finished());
+    if timed_out {
+        status.synchronize_timer = None;
+        status.leave("Could not reach every player".into());
+    }
+
+    let leave = status
+        .leave_timer
+        .as_mut()
+        .is_some_and(|timer| timer.tick(time.delta()).finished());
+    if leave {
+        next_state.set(GameState::MainMenu);
+    }
+
+    for mut text in texts.iter_mut() {
+        text.sections[0].value = status.message.clone().unwrap_or_default();
+    }
+}
+
+/**
+ * Ends the session and clears out the match, so a new one can be started from the menu.
+ */
+#[allow(clippy::type_complexity)]
+pub fn cleanup_session(
+    mut commands: Commands,
+    entities: Query<
+        Entity,
+        Or<(
+            With<Rollback>,
+            With<TileStorage>,
+            With<TilePos>,
+            With<Hazard>,
+            With<NetworkStatusText>,
+            With<NetStatsText>,
+            With<ReplayText>,
+            With<PauseMenuText>,
+            With<HudText>,
+        )>,
+    >,
+    mut cameras: Query<&mut Transform, With<Camera>>,
+) {
+    for entity in entities.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    for mut transform in cameras.iter_mut() {
+        transform.translation.x = 0.0;
+        transform.translation.y = 0.0;
+    }
+
+    commands.remove_resource::<Session<GgrsConfig>>();
+    commands.close_socket::<MultipleChannels>();
+    commands.remove_resource::<LocalPlayerHandle>();
+    commands.remove_resource::<PlayerClasses>();
+    commands.remove_resource::<PlayerNames>();
+    commands.remove_resource::<SpectatorCamera>();
+    commands.remove_resource::<NetworkStatus>();
+    commands.remove_resource::<LevelSeed>();
+    commands.remove_resource::<ReplayPlayback>();
+    commands.insert_resource(ChecksumHistory::default());
+    commands.insert_resource(StateChecksum::default());
+    commands.insert_resource(FrameCount::default());
+    commands.insert_resource(RollbackCounter::default());
+    commands.insert_resource(ReplayRecorder::default());
+    commands.insert_resource(PauseMenu::default());
+    commands.insert_resource(Depth::default());
+}
@@ -0,0 +1,394 @@
+use bevy::prelude::*;
+use bevy_ggrs::Session;
+
+use crate::{
+    chat::components::ChatInput,
+    config::GameMode,
+    settings::components::{SettingItem, Settings},
+    FontAssets, GameState, DARK, LIGHT,
+};
+
+use super::{components::GgrsConfig, net_stats::NetStatsOverlay};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PausePage {
+    #[default]
+    Main,
+    Settings,
+    Display,
+    Audio,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PauseItem {
+    Resume,
+    Settings,
+    Leave,
+    NetStats,
+    Display,
+    Audio,
+    Setting(SettingItem),
+    Back,
+}
+
+impl PausePage {
+    fn items(&self) -> &'static [PauseItem] {
+        match self {
+            PausePage::Main => &[PauseItem::Resume, PauseItem::Settings, PauseItem::Leave],
+            PausePage::Settings => &[
+                PauseItem::NetStats,
+                PauseItem::Display,
+                PauseItem::Audio,
+                PauseItem::Back,
+            ],
+            PausePage::Display => &[
+                PauseItem::Setting(SettingItem::WindowSize),
+                PauseItem::Setting(SettingItem::Fullscreen),
+                PauseItem::Setting(SettingItem::Vsync),
+                PauseItem::Setting(SettingItem::PixelScale),
+                PauseItem::Back,
+            ],
+            PausePage::Audio => &[
+                PauseItem::Setting(SettingItem::MasterVolume),
+                PauseItem::Setting(SettingItem::MusicVolume),
+                PauseItem::Setting(SettingItem::EffectsVolume),
+                PauseItem::Back,
+            ],
+        }
+    }
+
+    /**
+     * The page back and escape lead to.
+     */
+    fn parent(&self) -> PausePage {
+        match self {
+            PausePage::Display | PausePage::Audio => PausePage::Settings,
+            _ => PausePage::Main,
+        }
+    }
+}
+
+/**
+ * The in-game menu, escape opens and closes it.
+ */
+#[derive(Resource, Default)]
+pub struct PauseMenu {
+    pub open: bool,
+    page: PausePage,
+    selected: usize,
+    /// Local sessions are set aside while the menu is open, online matches keep going.
+    paused: Option<Session<GgrsConfig>>,
+}
+
+impl PauseMenu {
+    fn open_page(&mut self, page: PausePage) {
+        self.page = page;
+        self.selected = 0;
+    }
+}
+
+#[derive(Component)]
+pub struct PauseMenuText;
+
+#[derive(Component)]
+pub struct PauseButton(PauseItem);
+
+/**
+ * Up and down pick an item and enter picks it, hovering and clicking do the same. Left and right
+ * change settings.
+ */
+pub fn pause_menu_controls(
+    keyboard: Res<Input<KeyCode>>,
+    chat: Res<ChatInput>,
+    buttons: Query<(&Interaction, &PauseButton), Changed<Interaction>>,
+    mut menu: ResMut<PauseMenu>,
+    mut overlay: ResMut<NetStatsOverlay>,
+    mut settings: ResMut<Settings>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if chat.typing {
+        return;
+    }
+    if keyboard.just_pressed(KeyCode::Escape) {
+        match (menu.open, menu.page) {
+            (true, page) if page != PausePage::Main => menu.open_page(page.parent()),
+            (open, _) => {
+                menu.open = !open;
+                menu.open_page(PausePage::Main);
+            }
+        }
+        return;
+    }
+    if !menu.open {
+        return;
+    }
+
+    let items = menu.page.items();
+    let mut picked = None;
+    for (interaction, button) in buttons.iter() {
+        let Some(i) = items.iter().position(|item| *item == button.0) else {
+            continue;
+        };
+        if *interaction != Interaction::None && menu.selected != i {
+            menu.selected = i;
+        }
+        if *interaction == Interaction::Clicked {
+            picked = Some(button.0);
+        }
+    }
+    if keyboard.just_pressed(KeyCode::Up) {
+        menu.selected = menu.selected.checked_sub(1).unwrap_or(items.len() - 1);
+    } else if keyboard.just_pressed(KeyCode::Down) {
+        menu.selected = (menu.selected + 1) % items.len();
+    } else if keyboard.any_just_pressed([KeyCode::Return, KeyCode::Space]) {
+        picked = Some(items[menu.selected]);
+    } else if let PauseItem::Setting(setting) = items[menu.selected] {
+        if keyboard.just_pressed(KeyCode::Left) {
+            settings.adjust(setting, Some(-1));
+        } else if keyboard.just_pressed(KeyCode::Right) {
+            settings.adjust(setting, Some(1));
+        }
+    }
+
+    match picked {
+        Some(PauseItem::Resume) => menu.open = false,
+        Some(PauseItem::Settings) => menu.open_page(PausePage::Settings),
+        Some(PauseItem::Leave) => {
+            menu.open = false;
+            next_state.set(GameState::MainMenu);
+        }
+        Some(PauseItem::NetStats) => overlay.visible = !overlay.visible,
+        Some(PauseItem::Display) => menu.open_page(PausePage::Display),
+        Some(PauseItem::Audio) => menu.open_page(PausePage::Audio),
+        Some(PauseItem::Setting(setting)) => settings.adjust(setting, None),
+        Some(PauseItem::Back) => {
+            let parent = menu.page.parent();
+            menu.open_page(parent);
+        }
+        None => {}
+    }
+}
+
+/**
+ * Stops the simulation of solo games while the menu is open, nobody is waiting on us there.
+ *
+ * Without a session GGRS starts over and forgets its snapshots, which solo games never roll back
+ * to. Sync tests do, so they keep running like online matches.
+ */
+pub fn pause_local_session(world: &mut World) {
+    let solo = *world.resource::<GameMode>() == GameMode::Solo;
+    let menu = world.resource::<PauseMenu>();
+    let (open, paused) = (menu.open, menu.paused.is_some());
+
+    if open && solo && !paused {
+        if let Some(session) = world.remove_resource::<Session<GgrsConfig>>() {
+            world.resource_mut::<PauseMenu>().paused = Some(session);
+        }
+    } else if !open && paused {
+        let session = world.resource_mut::<PauseMenu>().paused.take();
+        if let Some(session) = session {
+            world.insert_resource(session);
+        }
+    }
+}
+
+fn item_label(item: PauseItem, overlay: &NetStatsOverlay, settings: &Settings) -> String {
+    match item {
+        PauseItem::Resume => "Resume".into(),
+        PauseItem::Settings => "Settings".into(),
+        PauseItem::Leave => "Leave Match".into(),
+        PauseItem::NetStats => {
+            let shown = if overlay.visible { "on" } else { "off" };
+            format!("Network stats: {shown}")
+        }
+        PauseItem::Display => "Display".into(),
+        PauseItem::Audio => "Audio".into(),
+        PauseItem::Setting(setting) => settings.label(setting),
+        PauseItem::Back => "Back".into(),
+    }
+}
+
+/**
+ * Builds the overlay again whenever something on it changes, it's gone while the menu is closed.
+ */
+#[allow(clippy::too_many_arguments)]
+pub fn update_pause_menu(
+    mut commands: Commands,
+    fonts: Res<FontAssets>,
+    mode: Res<GameMode>,
+    menu: Res<PauseMenu>,
+    overlay: Res<NetStatsOverlay>,
+    settings: Res<Settings>,
+    texts: Query<Entity, With<PauseMenuText>>,
+    mut net_stats_shown: Local<bool>,
+) {
+    // the overlay resource changes every frame, only whether it's shown is on the menu
+    if !menu.is_changed() && !settings.is_changed() && *net_stats_shown == overlay.visible {
+        return;
+    }
+    *net_stats_shown = overlay.visible;
+    for entity in texts.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    if !menu.open {
+        return;
+    }
+
+    let text_style = |font_size, color| TextStyle {
+        font: fonts.visitor.clone(),
+        font_size,
+        color,
+    };
+    let title = match *mode {
+        GameMode::Solo => "Paused",
+        // the others can't wait for us
+        _ => "The match goes on",
+    };
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                    position_type: PositionType::Absolute,
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                background_color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+                z_index: ZIndex::Global(1),
+                ..default()
+            },
+            PauseMenuText,
+        ))
+        .with_children(|parent| {
+            parent.spawn(
+                TextBundle::from_section(title, text_style(32.0, Color::WHITE)).with_style(Style {
+                    margin: UiRect::bottom(Val::Px(24.0)),
+                    ..default()
+                }),
+            );
+            for (i, item) in menu.page.items().iter().enumerate() {
+                let (background, color) = if i == menu.selected {
+                    (LIGHT, DARK)
+                } else {
+                    (Color::NONE, Color::WHITE)
+                };
+                parent
+                    .spawn((
+                        ButtonBundle {
+                            style: Style {
+                                size: Size::new(Val::Px(320.0), Val::Px(36.0)),
+                                justify_content: JustifyContent::Center,
+                                align_items: AlignItems::Center,
+                                margin: UiRect::all(Val::Px(4.0)),
+                                ..default()
+                            },
+                            background_color: background.into(),
+                            ..default()
+                        },
+                        PauseButton(*item),
+                    ))
+                    .with_children(|button| {
+                        button.spawn(TextBundle::from_section(
+                            item_label(*item, &overlay, &settings),
+                            text_style(24.0, color),
+                        ));
+                    });
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_ggrs::{
+        ggrs::{PlayerHandle, PlayerType, SessionBuilder},
+        GGRSPlugin, GGRSSchedule, RollbackIdProvider,
+    };
+    use std::{thread, time::Duration};
+
+    use super::*;
+    use crate::game::input::PlayerInput;
+
+    #[derive(Component, Reflect, Default)]
+    #[reflect(Component)]
+    struct Steps(u32);
+
+    fn no_input(_: In<PlayerHandle>) -> PlayerInput {
+        PlayerInput::default()
+    }
+
+    fn step(mut steps: Query<&mut Steps>) {
+        for mut steps in steps.iter_mut() {
+            steps.0 += 1;
+        }
+    }
+
+    /**
+     * A local session simulating one rollback entity, with the pause menu on top.
+     */
+    fn app(mode: GameMode, check_distance: usize) -> App {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins);
+        GGRSPlugin::<GgrsConfig>::new()
+            .with_input_system(no_input)
+            .register_rollback_component::<Steps>()
+            .build(&mut app);
+        app.add_system(step.in_schedule(GGRSSchedule))
+            .add_system(pause_local_session)
+            .insert_resource(mode)
+            .init_resource::<PauseMenu>();
+
+        let session = SessionBuilder::<GgrsConfig>::new()
+            .with_num_players(1)
+            .with_check_distance(check_distance)
+            .add_player(PlayerType::Local, 0)
+            .unwrap()
+            .start_synctest_session()
+            .unwrap();
+        app.insert_resource(Session::SyncTestSession(session));
+        let rollback = app.world.resource_mut::<RollbackIdProvider>().next();
+        app.world.spawn((Steps::default(), rollback));
+        app
+    }
+
+    /**
+     * Updates the app slowly enough for GGRS to simulate a frame almost every time.
+     */
+    fn run(app: &mut App, updates: usize) -> Option<u32> {
+        for _ in 0..updates {
+            thread::sleep(Duration::from_millis(20));
+            app.update();
+        }
+        let mut steps = app.world.query::<&Steps>();
+        steps.get_single(&app.world).ok().map(|steps| steps.0)
+    }
+
+    #[test]
+    fn sync_tests_keep_running_through_the_menu() {
+        let mut app = app(GameMode::SyncTest, 2);
+        let before = run(&mut app, 8).unwrap();
+
+        app.world.resource_mut::<PauseMenu>().open = true;
+        let open = run(&mut app, 8).unwrap();
+        assert!(open > before);
+        assert!(app.world.contains_resource::<Session<GgrsConfig>>());
+
+        app.world.resource_mut::<PauseMenu>().open = false;
+        assert!(run(&mut app, 8).unwrap() > open);
+    }
+
+    #[test]
+    fn solo_games_pause_and_resume() {
+        let mut app = app(GameMode::Solo, 0);
+        run(&mut app, 4).unwrap();
+
+        app.world.resource_mut::<PauseMenu>().open = true;
+        let paused = run(&mut app, 1).unwrap();
+        assert_eq!(run(&mut app, 8), Some(paused));
+
+        app.world.resource_mut::<PauseMenu>().open = false;
+        assert!(run(&mut app, 8).unwrap() > paused);
+    }
+}
@@ -0,0 +1,187 @@
+use super::{
+    classes::CharacterClass, fnv::Fnv64, levels::hash_generation_params, loot::LootTables,
+};
+use bevy::prelude::*;
+use bevy_matchbox::{
+    matchbox_socket::Packet,
+    prelude::{MultipleChannels, PeerId},
+    MatchboxSocket,
+};
+use serde::{Deserialize, Serialize};
+
+/**
+ * The version peers compare before playing together, taken from Cargo.toml.
+ */
+pub const GAME_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/**
+ * Unreliable channel handed over to GGRS once the session starts.
+ */
+pub const GGRS_CHANNEL: usize = 0;
+/**
+ * Reliable channel for everything peers need to tell each other outside of GGRS.
+ */
+pub const RELIABLE_CHANNEL: usize = 1;
+/**
+ * Reliable channel of its own for chat, so talking never holds up the lobby or checksums.
+ */
+pub const CHAT_CHANNEL: usize = 2;
+
+/**
+ * What peers have to agree on before a match between them stays in sync.
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Handshake {
+    pub version: String,
+    /// Hash of the gameplay data, see [`data_hash`].
+    pub data_hash: u64,
+    /// The level seed as this peer worked it out.
+    pub seed: u64,
+}
+
+impl Handshake {
+    /**
+     * Why a peer sending the other handshake can't play with us, in a way they can read.
+     */
+    pub fn incompatibility(&self, name: &str, other: &Handshake) -> Option<String> {
+        if self.version != other.version {
+            Some(format!(
+                "{} runs version {}, you run {}",
+                name, other.version, self.version
+            ))
+        } else if self.data_hash != other.data_hash {
+            Some(format!("{name} has different game data"))
+        } else {
+            None
+        }
+    }
+}
+
+/**
+ * Hash of everything besides the code that makes the simulation play out the same.
+ */
+pub fn data_hash(loot: &LootTables) -> u64 {
+    let mut hasher = Fnv64::new();
+    hash_generation_params(&mut hasher);
+    hasher.write_u64(loot.source_hash);
+    hasher.finish()
+}
+
+/**
+ * Everything the lobby shows about a peer.
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LobbyPeer {
+    pub name: String,
+    /// The class they'll play, spectators have none.
+    pub class: Option<CharacterClass>,
+    pub ready: bool,
+}
+
+/**
+ * How the match starts, sent by the first player once the countdown ends so every peer builds the
+ * same session.
+ */
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StartMatch {
+    /// Everyone in the lobby ordered by id, players get their handles in this order.
+    pub roster: Vec<(PeerId, LobbyPeer)>,
+    pub seed: u64,
+}
+
+impl StartMatch {
+    /**
+     * The peers playing the match, in handle order.
+     */
+    pub fn players(&self) -> impl Iterator<Item = &(PeerId, LobbyPeer)> {
+        self.roster.iter().filter(|(_, peer)| peer.class.is_some())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum PeerMessage {
+    /// Sent whenever the seed changes, kept first so builds that differ otherwise still read it.
+    Handshake(Handshake),
+    /// Sent to every peer in the lobby as soon as it connects and whenever something changes.
+    Lobby(LobbyPeer),
+    /// Sent by the first player to everyone else when the match starts.
+    Start(StartMatch),
+    /// Checksum of the world at the end of a confirmed frame.
+    Checksum { frame: u32, checksum: u64 },
+    /// Asks a peer whose checksum differs from ours for its state on that frame.
+    DumpRequest { frame: u32 },
+    /// The state of every rollback entity on a frame, answering a [`PeerMessage::DumpRequest`].
+    Dump { frame: u32, dump: String },
+}
+
+impl PeerMessage {
+    pub fn to_packet(&self) -> Packet {
+        bincode::serialize(self).unwrap().into_boxed_slice()
+    }
+
+    pub fn from_packet(packet: &[u8]) -> Option<PeerMessage> {
+        bincode::deserialize(packet).ok()
+    }
+}
+
+/**
+ * A line of chat, along with the name of whoever said it.
+ */
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub name: String,
+    pub text: String,
+}
+
+impl ChatMessage {
+    pub fn to_packet(&self) -> Packet {
+        bincode::serialize(self).unwrap().into_boxed_slice()
+    }
+
+    pub fn from_packet(packet: &[u8]) -> Option<ChatMessage> {
+        bincode::deserialize(packet).ok()
+    }
+}
+
+/**
+ * Sends the message to every connected peer on the reliable channel.
+ */
+pub fn broadcast(socket: &mut MatchboxSocket<MultipleChannels>, message: &PeerMessage) {
+    let peers: Vec<_> = socket.connected_peers().collect();
+    for peer in peers {
+        send(socket, peer, message);
+    }
+}
+
+/**
+ * Sends the message to a single peer on the reliable channel.
+ */
+pub fn send(socket: &mut MatchboxSocket<MultipleChannels>, peer: PeerId, message: &PeerMessage) {
+    socket
+        .channel(RELIABLE_CHANNEL)
+        .send(message.to_packet(), peer);
+}
+
+/**
+ * A message from a peer on the reliable channel, once the match has started.
+ */
+pub struct ReceivedMessage {
+    pub peer: PeerId,
+    pub message: PeerMessage,
+}
+
+pub fn receive_messages(
+    socket: Option<ResMut<MatchboxSocket<MultipleChannels>>>,
+    mut messages: EventWriter<ReceivedMessage>,
+) {
+    let Some(mut socket) = socket else {
+        return; // local games have nobody to talk to
+    };
+
+    for (peer, packet) in socket.channel(RELIABLE_CHANNEL).receive() {
+        match PeerMessage::from_packet(&packet) {
+            Some(message) => messages.send(ReceivedMessage { peer, message }),
+            None => warn!("Received an unknown message from {:?}", peer),
+        }
+    }
+}
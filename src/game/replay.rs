@@ -0,0 +1,333 @@
+use bevy::prelude::*;
+use bevy_ggrs::{ggrs::InputStatus, GGRSSchedule, PlayerInputs, Session};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::{DataAssets, FontAssets};
+
+use super::{
+    classes::{CharacterClass, PlayerClasses},
+    components::{FrameCount, GgrsConfig, LevelSeed, PlayerNames},
+    input::PlayerInput,
+    loot::LootTables,
+    protocol::{data_hash, GAME_VERSION},
+};
+
+/**
+ * Fastest a replay can be played back, as a multiple of the normal speed.
+ */
+pub const MAX_REPLAY_SPEED: u32 = 8;
+
+/**
+ * Everything needed to simulate a match again, frame by frame.
+ */
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Replay {
+    /// The game version and data hash of the recording, other builds may play it out differently.
+    pub version: String,
+    pub data_hash: u64,
+    pub seed: u64,
+    pub classes: Vec<CharacterClass>,
+    pub names: Vec<String>,
+    /// The confirmed inputs of every player, starting with the first frame.
+    pub inputs: Vec<Vec<PlayerInput>>,
+}
+
+impl Replay {
+    pub fn num_players(&self) -> usize {
+        self.classes.len()
+    }
+
+    /**
+     * Why the replay can't be played back the way it was recorded, if it can't.
+     */
+    pub fn incompatibility(&self, data_hash: u64) -> Option<String> {
+        if self.version != GAME_VERSION {
+            Some(format!(
+                "recorded with version {}, this is {}",
+                self.version, GAME_VERSION
+            ))
+        } else if self.data_hash != data_hash {
+            Some("recorded with different game data".into())
+        } else {
+            None
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load(path: &str) -> Result<Replay, String> {
+        let bytes = std::fs::read(path).map_err(|err| format!("failed to read {path}: {err}"))?;
+        bincode::deserialize(&bytes).map_err(|err| format!("invalid replay {path}: {err}"))
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn load(path: &str) -> Result<Replay, String> {
+        Err(format!("can't read {path} in the browser"))
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn save(&self) {
+        let seconds = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|time| time.as_secs())
+            .unwrap_or_default();
+        let path = format!("replay-{seconds}.replay");
+        let result = bincode::serialize(self)
+            .map_err(|err| err.to_string())
+            .and_then(|bytes| std::fs::write(&path, bytes).map_err(|err| err.to_string()));
+        match result {
+            Ok(()) => info!("Wrote {} frames to {}", self.inputs.len(), path),
+            Err(err) => error!("Failed writing {}: {}", path, err),
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn save(&self) {
+        warn!("Replays can't be saved in the browser");
+    }
+}
+
+/**
+ * The inputs of every simulated frame of the current match, written out when it ends.
+ */
+#[derive(Resource, Default)]
+pub struct ReplayRecorder {
+    inputs: BTreeMap<u32, Vec<PlayerInput>>,
+    /// Frames after this one may still be rolled back.
+    confirmed: Option<u32>,
+}
+
+/**
+ * A replay being played back, present instead of a recorder.
+ */
+#[derive(Resource)]
+pub struct ReplayPlayback {
+    pub replay: Replay,
+    pub speed: u32,
+    /// The session is set aside while paused, so GGRS doesn't advance.
+    paused: Option<Session<GgrsConfig>>,
+    /// The frame count when extra frames were last simulated.
+    last_frame: u32,
+    /// Kept from the last simulated frame to feed the frames simulated on top of GGRS.
+    inputs: Option<PlayerInputs<GgrsConfig>>,
+}
+
+impl ReplayPlayback {
+    pub fn new(replay: Replay) -> ReplayPlayback {
+        ReplayPlayback {
+            replay,
+            speed: 1,
+            paused: None,
+            last_frame: 0,
+            inputs: None,
+        }
+    }
+
+    /**
+     * The recorded input of the player in the frame, nothing once the replay has run out.
+     */
+    pub fn input(&self, frame: u32, handle: usize) -> PlayerInput {
+        (frame as usize)
+            .checked_sub(1)
+            .and_then(|i| self.replay.inputs.get(i))
+            .and_then(|inputs| inputs.get(handle))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    fn finished(&self, frame: u32) -> bool {
+        frame as usize >= self.replay.inputs.len()
+    }
+}
+
+#[derive(Component)]
+pub struct ReplayText;
+
+/**
+ * Remembers the inputs of the frame, frames simulated again after a rollback overwrite them.
+ */
+pub fn record_inputs(
+    frame: Res<FrameCount>,
+    inputs: Res<PlayerInputs<GgrsConfig>>,
+    mut recorder: ResMut<ReplayRecorder>,
+) {
+    recorder
+        .inputs
+        .insert(frame.0, inputs.iter().map(|(input, _)| *input).collect());
+}
+
+pub fn track_confirmed_frame(
+    session: Option<Res<Session<GgrsConfig>>>,
+    mut recorder: ResMut<ReplayRecorder>,
+) {
+    recorder.confirmed = match session.as_deref() {
+        Some(Session::P2PSession(session)) => u32::try_from(session.confirmed_frame()).ok(),
+        // local and spectated inputs are never predicted
+        _ => recorder.inputs.keys().next_back().copied(),
+    };
+}
+
+/**
+ * Writes the confirmed frames of the match to a replay file, unless it was a replay itself.
+ */
+pub fn save_replay(
+    recorder: Res<ReplayRecorder>,
+    playback: Option<Res<ReplayPlayback>>,
+    seed: Option<Res<LevelSeed>>,
+    classes: Option<Res<PlayerClasses>>,
+    names: Option<Res<PlayerNames>>,
+    data: Res<DataAssets>,
+    loot_tables: Res<Assets<LootTables>>,
+) {
+    let (None, Some(seed), Some(classes), Some(names), Some(confirmed), Some(loot)) = (
+        playback,
+        seed,
+        classes,
+        names,
+        recorder.confirmed,
+        loot_tables.get(&data.loot),
+    ) else {
+        return;
+    };
+
+    let inputs: Vec<_> = (1..=confirmed)
+        .map_while(|frame| recorder.inputs.get(&frame).cloned())
+        .collect();
+    if inputs.is_empty() {
+        return;
+    }
+
+    Replay {
+        version: GAME_VERSION.into(),
+        data_hash: data_hash(loot),
+        seed: seed.0,
+        classes: classes.0.clone(),
+        names: names.0.clone(),
+        inputs,
+    }
+    .save();
+}
+
+/**
+ * Takes the inputs GGRS is about to throw away, last thing in the GGRS schedule.
+ */
+pub fn keep_replay_inputs(world: &mut World) {
+    let inputs = world.remove_resource::<PlayerInputs<GgrsConfig>>();
+    world.resource_mut::<ReplayPlayback>().inputs = inputs;
+}
+
+pub fn setup_replay(mut commands: Commands, fonts: Res<FontAssets>) {
+    commands.spawn((
+        TextBundle::from_sections([TextSection::new(
+            "",
+            TextStyle {
+                font: fonts.visitor.clone(),
+                font_size: 16.0,
+                color: Color::WHITE,
+            },
+        )])
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                bottom: Val::Px(8.0),
+                left: Val::Px(8.0),
+                ..default()
+            },
+            ..default()
+        }),
+        ReplayText,
+    ));
+}
+
+/**
+ * P pauses, [ and ] change the speed, the replay pauses by itself when it runs out of inputs.
+ */
+pub fn replay_controls(world: &mut World) {
+    let frame = world.resource::<FrameCount>().0;
+    let (pause, slower, faster) = {
+        let keys = world.resource::<Input<KeyCode>>();
+        (
+            keys.just_pressed(KeyCode::P),
+            keys.just_pressed(KeyCode::LBracket),
+            keys.just_pressed(KeyCode::RBracket),
+        )
+    };
+
+    let mut playback = world.resource_mut::<ReplayPlayback>();
+    if slower {
+        playback.speed = u32::max(playback.speed / 2, 1);
+    } else if faster {
+        playback.speed = u32::min(playback.speed * 2, MAX_REPLAY_SPEED);
+    }
+
+    let paused = playback.paused.is_some();
+    let finished = playback.finished(frame);
+    if paused && pause && !finished {
+        let session = playback.paused.take();
+        if let Some(session) = session {
+            world.insert_resource(session);
+        }
+    } else if !paused && (pause || finished) {
+        if let Some(session) = world.remove_resource::<Session<GgrsConfig>>() {
+            world.resource_mut::<ReplayPlayback>().paused = Some(session);
+        }
+    }
+}
+
+/**
+ * Simulates extra frames for every frame GGRS simulated when playing faster than normal.
+ */
+pub fn fast_forward(world: &mut World) {
+    let frame = world.resource::<FrameCount>().0;
+    let mut playback = world.resource_mut::<ReplayPlayback>();
+    let simulated = frame.saturating_sub(playback.last_frame);
+    let mut extra = simulated * (playback.speed - 1);
+    playback.last_frame = frame;
+    if playback.paused.is_some() {
+        return;
+    }
+
+    while extra > 0 {
+        let mut playback = world.resource_mut::<ReplayPlayback>();
+        let next = playback.last_frame + 1;
+        if playback.finished(playback.last_frame) {
+            break;
+        }
+        let Some(mut inputs) = playback.inputs.take() else {
+            break; // GGRS hasn't simulated a frame yet
+        };
+        let handles = playback.replay.num_players();
+        *inputs = (0..handles)
+            .map(|handle| (playback.input(next, handle), InputStatus::Confirmed))
+            .collect();
+        playback.last_frame = next;
+
+        world.insert_resource(inputs);
+        world.run_schedule(GGRSSchedule);
+        extra -= 1;
+    }
+}
+
+pub fn update_replay_text(
+    frame: Res<FrameCount>,
+    playback: Res<ReplayPlayback>,
+    mut texts: Query<&mut Text, With<ReplayText>>,
+) {
+    let state = if playback.finished(frame.0) {
+        "finished".to_string()
+    } else if playback.paused.is_some() {
+        "paused".to_string()
+    } else {
+        format!("x{}", playback.speed)
+    };
+    let label = format!(
+        "Replay {} - frame {}/{}\nP to pause, [ ] to change speed",
+        state,
+        frame.0,
+        playback.replay.inputs.len()
+    );
+
+    for mut text in texts.iter_mut() {
+        text.sections[0].value = label.clone();
+    }
+}
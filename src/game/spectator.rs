@@ -0,0 +1,102 @@
+use bevy::prelude::*;
+
+use crate::chat::components::ChatInput;
+
+use super::components::Player;
+
+/**
+ * Units per second the free camera pans.
+ */
+pub const FREE_CAMERA_SPEED: f32 = 300.0;
+
+/**
+ * Where a spectator is looking, only present when spectating.
+ */
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpectatorCamera {
+    /// Keeps the player with this handle in the middle of the screen.
+    Follow(usize),
+    /// Panned around with the movement keys.
+    Free,
+}
+
+impl Default for SpectatorCamera {
+    fn default() -> Self {
+        SpectatorCamera::Follow(0)
+    }
+}
+
+/**
+ * Tab cycles through the players to follow, F switches to and from the free camera.
+ */
+pub fn spectator_controls(
+    keyboard: Res<Input<KeyCode>>,
+    chat: Res<ChatInput>,
+    mut camera: ResMut<SpectatorCamera>,
+    players: Query<&Player>,
+) {
+    if chat.typing {
+        return;
+    }
+    let num_players = players.iter().count().max(1);
+    if keyboard.just_pressed(KeyCode::F) {
+        *camera = match *camera {
+            SpectatorCamera::Follow(_) => SpectatorCamera::Free,
+            SpectatorCamera::Free => SpectatorCamera::Follow(0),
+        };
+    } else if keyboard.just_pressed(KeyCode::Tab) {
+        *camera = match *camera {
+            SpectatorCamera::Follow(handle) => SpectatorCamera::Follow((handle + 1) % num_players),
+            SpectatorCamera::Free => SpectatorCamera::Follow(0),
+        };
+    }
+}
+
+pub fn spectator_camera(
+    time: Res<Time>,
+    keyboard: Res<Input<KeyCode>>,
+    chat: Res<ChatInput>,
+    camera: Res<SpectatorCamera>,
+    players: Query<(&Player, &Transform)>,
+    mut camera_query: Query<&mut Transform, (With<Camera>, Without<Player>)>,
+) {
+    let target = match *camera {
+        SpectatorCamera::Follow(handle) => players
+            .iter()
+            .find(|(player, _)| player.handle == handle)
+            .map(|(_, transform)| transform.translation.truncate()),
+        SpectatorCamera::Free => None,
+    };
+
+    let mut pan = Vec2::ZERO;
+    if keyboard.any_pressed([KeyCode::Left, KeyCode::A]) {
+        pan.x -= 1.0;
+    }
+    if keyboard.any_pressed([KeyCode::Right, KeyCode::D]) {
+        pan.x += 1.0;
+    }
+    if keyboard.any_pressed([KeyCode::Up, KeyCode::W]) {
+        pan.y += 1.0;
+    }
+    if keyboard.any_pressed([KeyCode::Down, KeyCode::S]) {
+        pan.y -= 1.0;
+    }
+    if chat.typing {
+        pan = Vec2::ZERO; // the keys are spelling out a message
+    }
+
+    for mut camera_transform in camera_query.iter_mut() {
+        match target {
+            Some(pos) => {
+                camera_transform.translation.x = pos.x;
+                camera_transform.translation.y = pos.y;
+            }
+            None if *camera == SpectatorCamera::Free => {
+                let delta = pan.normalize_or_zero() * FREE_CAMERA_SPEED * time.delta_seconds();
+                camera_transform.translation += delta.extend(0.0);
+            }
+            // the followed player isn't around, leave the camera be
+            None => {}
+        }
+    }
+}
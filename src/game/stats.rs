@@ -0,0 +1,138 @@
+use bevy::prelude::*;
+use std::ops::Add;
+
+/**
+ * Experience needed to go from level 1 to 2, every following level needs this much more.
+ */
+pub const XP_PER_LEVEL: u32 = 20;
+
+/**
+ * Stats gained on every level up.
+ */
+pub const LEVEL_UP_GAINS: Stats = Stats {
+    strength: 1,
+    agility: 1,
+    vitality: 1,
+    intellect: 1,
+};
+
+#[derive(Debug, Component, Reflect, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[reflect(Component, Hash)]
+pub struct Stats {
+    pub strength: u32,
+    pub agility: u32,
+    pub vitality: u32,
+    pub intellect: u32,
+}
+
+impl Stats {
+    pub const fn new(strength: u32, agility: u32, vitality: u32, intellect: u32) -> Stats {
+        Stats {
+            strength,
+            agility,
+            vitality,
+            intellect,
+        }
+    }
+
+    /**
+     * Units moved per frame.
+     */
+    pub fn move_speed(&self) -> f32 {
+        1.5 + self.agility as f32 * 0.1
+    }
+
+    pub fn max_health(&self) -> u32 {
+        50 + self.vitality * 10
+    }
+
+    pub fn attack_damage(&self) -> u32 {
+        5 + self.strength * 2
+    }
+
+    pub fn spell_damage(&self) -> u32 {
+        5 + self.intellect * 3
+    }
+
+    /**
+     * Frames between attacks, quicker with more agility but never below 10.
+     */
+    pub fn attack_cooldown(&self) -> u32 {
+        u32::max(10, 40u32.saturating_sub(self.agility * 2))
+    }
+}
+
+impl Add for Stats {
+    type Output = Stats;
+
+    fn add(self, other: Stats) -> Stats {
+        Stats {
+            strength: self.strength + other.strength,
+            agility: self.agility + other.agility,
+            vitality: self.vitality + other.vitality,
+            intellect: self.intellect + other.intellect,
+        }
+    }
+}
+
+#[derive(Debug, Component, Reflect, Default, Clone, Copy, Hash)]
+#[reflect(Component, Hash)]
+pub struct Health {
+    pub current: u32,
+    pub max: u32,
+}
+
+impl Health {
+    pub fn full(max: u32) -> Health {
+        Health { current: max, max }
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.current == 0
+    }
+
+    pub fn damage(&mut self, amount: u32) {
+        self.current = self.current.saturating_sub(amount);
+    }
+
+    pub fn heal(&mut self, amount: u32) {
+        self.current = u32::min(self.max, self.current + amount);
+    }
+}
+
+#[derive(Debug, Component, Reflect, Clone, Copy, Hash)]
+#[reflect(Component, Hash)]
+pub struct Experience {
+    pub level: u32,
+    pub xp: u32,
+}
+
+impl Default for Experience {
+    fn default() -> Self {
+        Experience { level: 1, xp: 0 }
+    }
+}
+
+impl Experience {
+    pub fn next_level_xp(&self) -> u32 {
+        XP_PER_LEVEL * self.level
+    }
+}
+
+/**
+ * Levels up everyone with enough experience, growing their stats and max health.
+ */
+pub fn level_up(mut query: Query<(&mut Experience, &mut Stats, &mut Health)>) {
+    for (mut experience, mut stats, mut health) in query.iter_mut() {
+        while experience.xp >= experience.next_level_xp() {
+            experience.xp -= experience.next_level_xp();
+            experience.level += 1;
+            *stats = *stats + LEVEL_UP_GAINS;
+
+            let gained = stats.max_health() - health.max;
+            health.max += gained;
+            health.heal(gained);
+            info!("Reached level {}", experience.level);
+        }
+    }
+}
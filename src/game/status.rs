@@ -0,0 +1,238 @@
+use bevy::prelude::*;
+
+use super::{
+    collision::circles_touching, combat::LastHitBy, components::Bounding, effects::Darken,
+    stats::Health,
+};
+
+/**
+ * Frames a hazard keeps its effect going after the entity steps off it.
+ */
+pub const HAZARD_LINGER_FRAMES: u32 = 60;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Reflect, FromReflect)]
+pub enum StatusKind {
+    #[default]
+    Poison,
+    Burn,
+    Slow,
+    Stun,
+}
+
+impl StatusKind {
+    pub fn max_stacks(&self) -> u32 {
+        match self {
+            StatusKind::Poison => 5,
+            StatusKind::Burn => 3,
+            StatusKind::Slow => 3,
+            StatusKind::Stun => 1,
+        }
+    }
+
+    /**
+     * Frames between each time the effect deals damage, 0 if it never does.
+     */
+    pub fn tick_interval(&self) -> u32 {
+        match self {
+            StatusKind::Poison => 30,
+            StatusKind::Burn => 15,
+            _ => 0,
+        }
+    }
+
+    pub fn damage_per_stack(&self) -> u32 {
+        match self {
+            StatusKind::Poison => 2,
+            StatusKind::Burn => 3,
+            _ => 0,
+        }
+    }
+
+    pub fn tint(&self) -> Color {
+        match self {
+            StatusKind::Poison => Color::rgb(0.5, 1.0, 0.5),
+            StatusKind::Burn => Color::rgb(1.0, 0.6, 0.4),
+            StatusKind::Slow => Color::rgb(0.6, 0.7, 1.0),
+            StatusKind::Stun => Color::rgb(1.0, 1.0, 0.5),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Reflect, FromReflect)]
+pub struct StatusEffect {
+    pub kind: StatusKind,
+    pub stacks: u32,
+    pub frames_left: u32,
+    pub frames_since_tick: u32,
+    /// Handle of the player who applied the effect, credited for kills.
+    pub source: Option<usize>,
+}
+
+#[derive(Debug, Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct StatusEffects(pub Vec<StatusEffect>);
+
+impl StatusEffects {
+    /**
+     * Adds a stack of the effect and refreshes its duration.
+     */
+    pub fn apply(&mut self, kind: StatusKind, frames: u32, source: Option<usize>) {
+        match self.0.iter_mut().find(|e| e.kind == kind) {
+            Some(effect) => {
+                effect.stacks = u32::min(effect.stacks + 1, kind.max_stacks());
+                effect.frames_left = u32::max(effect.frames_left, frames);
+                effect.source = source.or(effect.source);
+            }
+            None => self.0.push(StatusEffect {
+                kind,
+                stacks: 1,
+                frames_left: frames,
+                frames_since_tick: 0,
+                source,
+            }),
+        }
+    }
+
+    /**
+     * Refreshes the duration of the effect without adding a stack.
+     */
+    pub fn refresh(&mut self, kind: StatusKind, frames: u32) {
+        match self.0.iter_mut().find(|e| e.kind == kind) {
+            Some(effect) => effect.frames_left = u32::max(effect.frames_left, frames),
+            None => self.apply(kind, frames, None),
+        }
+    }
+
+    pub fn stacks(&self, kind: StatusKind) -> u32 {
+        self.0
+            .iter()
+            .find(|e| e.kind == kind)
+            .map_or(0, |e| e.stacks)
+    }
+
+    pub fn is_stunned(&self) -> bool {
+        self.stacks(StatusKind::Stun) > 0
+    }
+
+    pub fn move_speed_multiplier(&self) -> f32 {
+        if self.is_stunned() {
+            return 0.0;
+        }
+
+        f32::max(0.4, 1.0 - 0.2 * self.stacks(StatusKind::Slow) as f32)
+    }
+}
+
+/**
+ * Lasting hurt applied to everything standing on it.
+ */
+#[derive(Debug, Component)]
+pub struct Hazard(pub StatusKind);
+
+pub fn spawn_hazard(
+    commands: &mut Commands,
+    atlas: Handle<TextureAtlas>,
+    sprite: usize,
+    kind: StatusKind,
+    position: Vec2,
+) {
+    commands.spawn((
+        SpriteSheetBundle {
+            texture_atlas: atlas,
+            sprite: TextureAtlasSprite {
+                index: sprite,
+                color: kind.tint().with_a(0.6),
+                ..default()
+            },
+            transform: Transform {
+                translation: position.extend(0.5),
+                scale: Vec3::new(2.0, 2.0, 2.0),
+                ..default()
+            },
+            ..default()
+        },
+        Hazard(kind),
+        Bounding(12.0),
+    ));
+}
+
+pub fn hazard_effects(
+    hazards: Query<(&Hazard, &Transform, &Bounding)>,
+    mut affected: Query<(&mut StatusEffects, &Transform, &Bounding), Without<Hazard>>,
+) {
+    for (mut effects, transform, bounding) in affected.iter_mut() {
+        for (hazard, hazard_transform, hazard_bounding) in hazards.iter() {
+            if circles_touching(transform, bounding, hazard_transform, hazard_bounding) {
+                effects.refresh(hazard.0, HAZARD_LINGER_FRAMES);
+            }
+        }
+    }
+}
+
+/**
+ * Steps every status effect one frame, dealing tick damage and dropping expired effects.
+ */
+pub fn step_status_effects(
+    mut query: Query<(&mut StatusEffects, &mut Health, Option<&mut LastHitBy>)>,
+) {
+    for (mut effects, mut health, mut last_hit_by) in query.iter_mut() {
+        for effect in effects.0.iter_mut() {
+            effect.frames_left = effect.frames_left.saturating_sub(1);
+
+            let interval = effect.kind.tick_interval();
+            if interval == 0 {
+                continue;
+            }
+
+            effect.frames_since_tick += 1;
+            if effect.frames_since_tick >= interval {
+                effect.frames_since_tick = 0;
+                health.damage(effect.kind.damage_per_stack() * effect.stacks);
+                if let (Some(last_hit_by), Some(source)) = (last_hit_by.as_mut(), effect.source) {
+                    last_hit_by.0 = Some(source);
+                }
+            }
+        }
+
+        effects.0.retain(|e| e.frames_left > 0);
+    }
+}
+
+/**
+ * Tints entities by their most recently applied status effect, leaving sprites without effects
+ * to whatever colors them.
+ */
+pub fn tint_status_effects(
+    mut commands: Commands,
+    mut query: Query<(
+        Entity,
+        &StatusEffects,
+        &mut TextureAtlasSprite,
+        Option<&mut Darken>,
+    )>,
+) {
+    for (entity, effects, mut sprite, darken) in query.iter_mut() {
+        match (effects.0.last(), darken) {
+            (Some(effect), Some(mut darken)) => {
+                let tint = effect.kind.tint();
+                if darken.tint != tint {
+                    darken.tint = tint;
+                    sprite.color = tint;
+                }
+            }
+            (Some(effect), None) => {
+                let tint = effect.kind.tint();
+                commands.entity(entity).insert(Darken {
+                    tint,
+                    original: sprite.color,
+                });
+                sprite.color = tint;
+            }
+            (None, Some(darken)) => {
+                sprite.color = darken.original;
+                commands.entity(entity).remove::<Darken>();
+            }
+            (None, None) => {}
+        }
+    }
+}
@@ -1,19 +1,30 @@
 use bevy::{math::vec2, prelude::*};
 use bevy_ecs_tilemap::prelude::*;
-use bevy_ggrs::{ggrs::PlayerType, *};
-use bevy_matchbox::{prelude::SingleChannel, MatchboxSocket};
-use bevy_turborand::{GlobalRng, RngComponent};
-use std::time::Duration;
+use bevy_ggrs::*;
+use bevy_turborand::RngComponent;
 
-use crate::{game::components::LocalPlayerHandle, FontAssets, GameState, ImageAssets};
+use crate::{game::components::LocalPlayerHandle, ImageAssets};
 
 use super::{
-    components::{AnimationIndices, AnimationTimer, ExampleGameText, GgrsConfig, Player, Pos, Vel},
+    classes::{CharacterBundle, Dashing, Facing, PlayerClasses, DASH_SPEED_MULTIPLIER},
+    combat::spawn_enemy,
+    components::{
+        AnimationIndices, AnimationTimer, Bounding, ExampleGameText, GgrsConfig, LevelSeed, Outfit,
+        Player, PlayerNames, Pos, SpawnPoints, Vel,
+    },
     effects::Flick,
-    input::direction,
     levels,
+    loot::spawn_chest,
+    stats::Stats,
+    status::{spawn_hazard, StatusEffects, StatusKind},
 };
 
+/**
+ * GGRS frames between animation steps for standing still and walking.
+ */
+pub const IDLE_ANIMATION_FRAMES: u32 = 15;
+pub const WALK_ANIMATION_FRAMES: u32 = 9;
+
 pub fn camera_follow(
     player_handle: Option<Res<LocalPlayerHandle>>,
     player_query: Query<(&Player, &Transform)>,
@@ -37,146 +48,112 @@ pub fn camera_follow(
     }
 }
 
+#[allow(clippy::type_complexity)]
 pub fn move_players(
     inputs: Res<PlayerInputs<GgrsConfig>>,
     mut player: Query<(
         &Player,
+        &Outfit,
+        &Stats,
+        &StatusEffects,
+        &Dashing,
+        &mut Facing,
         &mut Transform,
         &mut AnimationIndices,
         &mut TextureAtlasSprite,
         &mut AnimationTimer,
     )>,
 ) {
-    for (player, mut transform, mut indices, mut sprite, mut timer) in player.iter_mut() {
+    for (
+        player,
+        outfit,
+        stats,
+        effects,
+        dashing,
+        mut facing,
+        mut transform,
+        mut indices,
+        mut sprite,
+        mut timer,
+    ) in player.iter_mut()
+    {
         let (input, _) = inputs[player.handle];
-        let direction = direction(input);
-        let move_speed = 2.0;
+        let mut direction = input.direction();
+        let mut move_speed = stats.move_speed() * effects.move_speed_multiplier();
+        if dashing.0 > 0 {
+            // dashes keep going the way they started
+            direction = facing.0;
+            move_speed *= DASH_SPEED_MULTIPLIER;
+        }
         let move_delta = (direction * move_speed).extend(0.0);
 
-        if direction == Vec2::ZERO {
+        if direction == Vec2::ZERO || move_speed == 0.0 {
             // update animation
-            indices.first = 0;
-            indices.last = 1;
+            indices.first = outfit.sprite(0);
+            indices.last = outfit.sprite(1);
             sprite.index = usize::clamp(sprite.index, indices.first, indices.last);
-            timer.0.set_duration(Duration::from_millis(250));
+            timer.frames_per_step = IDLE_ANIMATION_FRAMES;
             continue;
         }
 
         transform.translation += move_delta;
+        facing.0 = direction.normalize();
 
         // update animatio
-        indices.first = 2;
-        indices.last = 3;
+        indices.first = outfit.sprite(2);
+        indices.last = outfit.sprite(3);
         sprite.index = usize::clamp(sprite.index, indices.first, indices.last);
         if move_delta.x < 0.0 {
             sprite.flip_x = true;
         } else if move_delta.x > 0.0 {
             sprite.flip_x = false;
         }
-        timer.0.set_duration(Duration::from_millis(150));
-    }
-}
-
-pub fn wait_for_players(
-    mut commands: Commands,
-    mut socket: ResMut<MatchboxSocket<SingleChannel>>,
-    mut next_state: ResMut<NextState<GameState>>,
-) {
-    if socket.get_channel(0).is_err() {
-        return; // we've already started
-    }
-
-    // check for new connections
-    socket.update_peers();
-    let players = socket.players();
-
-    let num_players = 2;
-    if players.len() < num_players {
-        return; // wait for more players
-    }
-
-    info!("All peers have joined, going in-game");
-
-    // create a GGRS P2P session
-    let mut session_builder = ggrs::SessionBuilder::<GgrsConfig>::new()
-        .with_num_players(num_players)
-        .with_input_delay(2);
-
-    for (i, player) in players.into_iter().enumerate() {
-        if player == PlayerType::Local {
-            commands.insert_resource(LocalPlayerHandle(i));
-        }
-        session_builder = session_builder
-            .add_player(player, i)
-            .expect("failed to add player");
+        timer.frames_per_step = WALK_ANIMATION_FRAMES;
     }
-
-    // move the channel out of the socket (required because GGRS takes ownership of it)
-    let channel = socket.take_channel(0).unwrap();
-
-    // start the GGRS session
-    let ggrs_session = session_builder
-        .start_p2p_session(channel)
-        .expect("failed to start session");
-
-    commands.insert_resource(bevy_ggrs::Session::P2PSession(ggrs_session));
-
-    // transition to the state InGame
-    next_state.set(GameState::InGame);
 }
 
 pub fn spawn_player(
     mut commands: Commands,
     images: Res<ImageAssets>,
+    classes: Res<PlayerClasses>,
+    names: Res<PlayerNames>,
+    spawn_points: Res<SpawnPoints>,
     mut rip: ResMut<RollbackIdProvider>,
 ) {
-    let anim_indices = AnimationIndices { first: 0, last: 1 };
-
-    // Spawn player 1
-    commands.spawn((
-        SpriteSheetBundle {
-            texture_atlas: images.char_idle.clone(),
-            sprite: TextureAtlasSprite::new(0),
-            transform: Transform {
-                translation: Vec3::new(-16., 0., 0.0),
-                scale: Vec3::new(3.0, 3.0, 3.0),
+    for (handle, class) in classes.0.iter().enumerate() {
+        let outfit = Outfit::for_handle(handle);
+        let position = spawn_points.0.get(handle).copied().unwrap_or_default();
+        commands.spawn((
+            SpriteSheetBundle {
+                texture_atlas: class.atlas(&images),
+                sprite: TextureAtlasSprite::new(outfit.sprite(0)),
+                transform: Transform {
+                    translation: position.extend(1.0),
+                    scale: Vec3::new(3.0, 3.0, 3.0),
+                    ..default()
+                },
                 ..default()
             },
-            ..default()
-        },
-        anim_indices.clone(),
-        AnimationTimer(Timer::from_seconds(0.25, TimerMode::Repeating)),
-        Player { handle: 0 },
-        rip.next(),
-    ));
-
-    // Spawn player 2
-    commands.spawn((
-        SpriteSheetBundle {
-            texture_atlas: images.char_idle.clone(),
-            sprite: TextureAtlasSprite {
-                index: 0,
-                color: Color::rgb(0.5, 0.5, 1.0),
-                ..default()
-            },
-            transform: Transform {
-                translation: Vec3::new(16., 0., 0.0),
-                scale: Vec3::new(3.0, 3.0, 3.0),
-                ..default()
+            AnimationIndices {
+                first: outfit.sprite(0),
+                last: outfit.sprite(1),
             },
-            ..default()
-        },
-        anim_indices,
-        AnimationTimer(Timer::from_seconds(0.25, TimerMode::Repeating)),
-        Player { handle: 1 },
-        rip.next(),
-    ));
+            AnimationTimer::new(IDLE_ANIMATION_FRAMES),
+            Bounding(16.0),
+            CharacterBundle::new(*class),
+            outfit,
+            Player { handle },
+            Name::new(names.0[handle].clone()),
+            rip.next(),
+        ));
+    }
 }
 
 pub fn setup_level(
     mut commands: Commands,
     images: Res<ImageAssets>,
-    mut global_rng: ResMut<GlobalRng>,
+    seed: Res<LevelSeed>,
+    mut rip: ResMut<RollbackIdProvider>,
 ) {
     // Size of the tile map in tiles.
     let map_size = TilemapSize { x: 64, y: 64 };
@@ -200,8 +177,8 @@ pub fn setup_level(
     let grid_size = tile_size.into();
     let map_type = TilemapType::default();
 
-    let mut rng = RngComponent::from(&mut global_rng);
-    levels::cave(
+    let mut rng = RngComponent::with_seed(seed.0);
+    let rooms = levels::cave(
         &mut rng,
         &mut commands,
         &tilemap_entity,
@@ -209,6 +186,7 @@ pub fn setup_level(
         &mut tile_storage,
     );
 
+    let transform = get_tilemap_center_transform(&map_size, &grid_size, &map_type, 0.0);
     commands.entity(tilemap_entity).insert(TilemapBundle {
         grid_size,
         map_type,
@@ -216,9 +194,42 @@ pub fn setup_level(
         storage: tile_storage,
         texture: TilemapTexture::Single(images.atlas.clone()),
         tile_size,
-        transform: get_tilemap_center_transform(&map_size, &grid_size, &map_type, 0.0),
+        transform,
         ..Default::default()
     });
+
+    // players start out in the first room
+    let spawn_points = rooms[0]
+        .spawn_points()
+        .iter()
+        .map(|tile| transform.translation.truncate() + tile.center_in_world(&grid_size, &map_type))
+        .collect();
+    commands.insert_resource(SpawnPoints(spawn_points));
+
+    // the other rooms alternate between holding a chest and an enemy
+    for (i, room) in rooms.iter().enumerate().skip(1) {
+        let position =
+            transform.translation.truncate() + room.center().center_in_world(&grid_size, &map_type);
+        if i % 2 == 0 {
+            spawn_chest(&mut commands, &mut rip, &images, position);
+        } else {
+            spawn_enemy(&mut commands, &mut rip, &images, position);
+        }
+
+        // and every third room has a poisonous puddle in a corner
+        if i % 3 == 2 {
+            let corner = TilePos::new(room.pos.x + 1, room.pos.y + 1);
+            let position =
+                transform.translation.truncate() + corner.center_in_world(&grid_size, &map_type);
+            spawn_hazard(
+                &mut commands,
+                images.tiles.clone(),
+                levels::CaveAtlasIndices::CaveFloor3_d as usize,
+                StatusKind::Poison,
+                position,
+            );
+        }
+    }
 }
 
 pub fn teardown(mut commands: Commands, texts: Query<(Entity, With<ExampleGameText>)>) {
@@ -227,8 +238,10 @@ pub fn teardown(mut commands: Commands, texts: Query<(Entity, With<ExampleGameTe
     }
 }
 
+/**
+ * Steps sprite animations, runs in the GGRS schedule so animations survive rollbacks.
+ */
 pub fn animate_sprite(
-    time: Res<Time>,
     mut query: Query<(
         &AnimationIndices,
         &mut AnimationTimer,
@@ -236,8 +249,7 @@ pub fn animate_sprite(
     )>,
 ) {
     for (indices, mut timer, mut sprite) in &mut query {
-        timer.tick(time.delta());
-        if timer.just_finished() {
+        if timer.tick() {
             sprite.index = if sprite.index == indices.last {
                 indices.first
             } else {
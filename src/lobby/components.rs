@@ -0,0 +1,87 @@
+use bevy::{prelude::*, utils::HashMap};
+use bevy_matchbox::prelude::PeerId;
+
+use crate::game::protocol::{Handshake, LobbyPeer, StartMatch};
+
+#[derive(Component)]
+pub struct LobbyText;
+
+#[derive(Component)]
+pub struct PeerListText;
+
+/**
+ * Everyone in the room and how far along they are to starting the match.
+ */
+#[derive(Resource)]
+pub struct Lobby {
+    pub local: LobbyPeer,
+    pub peers: HashMap<PeerId, LobbyPeer>,
+    /// Counting down to the match once everyone is ready.
+    pub countdown: Option<Timer>,
+    pub data_hash: u64,
+    /// The handshake last sent to the peers.
+    pub handshake: Option<Handshake>,
+    pub handshakes: HashMap<PeerId, Handshake>,
+    /// Why the last countdown didn't end in a match.
+    pub refusal: Option<String>,
+    /// The match a peer started for us, and who that was.
+    pub start: Option<(PeerId, StartMatch)>,
+}
+
+impl Lobby {
+    pub fn everyone_ready(&self, expected_peers: usize) -> bool {
+        self.peers.len() + 1 >= expected_peers
+            && self.local.ready
+            && self.peers.values().all(|peer| peer.ready)
+            && self
+                .peers
+                .keys()
+                .all(|peer| self.handshakes.contains_key(peer))
+    }
+
+    /**
+     * Whether anyone in the lobby picked a class, spectators are ready without one.
+     */
+    pub fn has_players(&self) -> bool {
+        self.local.class.is_some() || self.peers.values().any(|peer| peer.class.is_some())
+    }
+
+    /**
+     * Why the players in the lobby don't make up the match, once everyone is there.
+     */
+    pub fn player_count_mismatch(&self, expected: usize) -> Option<String> {
+        let players = self
+            .peers
+            .values()
+            .chain(std::iter::once(&self.local))
+            .filter(|peer| peer.class.is_some())
+            .count();
+        (players != expected)
+            .then(|| format!("expected {expected} players, but {players} want to play"))
+    }
+
+    fn peer_name(&self, peer: &PeerId) -> &str {
+        self.peers.get(peer).map_or("A peer", |peer| &peer.name)
+    }
+
+    /**
+     * Why we can't play with someone in the lobby, if there is anyone.
+     */
+    pub fn incompatibility(&self) -> Option<String> {
+        let local = self.handshake.as_ref()?;
+        self.handshakes
+            .iter()
+            .find_map(|(peer, other)| local.incompatibility(self.peer_name(peer), other))
+    }
+
+    /**
+     * Someone who worked out another level seed, they'd be playing a different dungeon.
+     */
+    pub fn seed_disagreement(&self) -> Option<String> {
+        let local = self.handshake.as_ref()?;
+        self.handshakes
+            .iter()
+            .find(|(_, other)| other.seed != local.seed)
+            .map(|(peer, _)| format!("{} disagrees on the level seed", self.peer_name(peer)))
+    }
+}
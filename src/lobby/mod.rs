@@ -0,0 +1,21 @@
+use self::systems::{countdown, lobby_controls, setup, sync_lobby, teardown, update_peer_list};
+use crate::GameState;
+use bevy::prelude::*;
+mod components;
+mod systems;
+
+/**
+ * Shows who's in the room and starts the match once every player is ready.
+ */
+pub struct LobbyPlugin;
+impl Plugin for LobbyPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(setup.in_schedule(OnEnter(GameState::Lobby)))
+            .add_systems(
+                (sync_lobby, lobby_controls, countdown, update_peer_list)
+                    .chain()
+                    .in_set(OnUpdate(GameState::Lobby)),
+            )
+            .add_system(teardown.in_schedule(OnExit(GameState::Lobby)));
+    }
+}
@@ -0,0 +1,427 @@
+use bevy::prelude::*;
+use bevy_ggrs::ggrs::{self, PlayerType};
+use bevy_matchbox::{
+    prelude::{MultipleChannels, PeerId, PeerState},
+    MatchboxSocket,
+};
+
+use crate::{
+    chat::components::ChatInput,
+    config::{MatchConfig, PlayerName},
+    game::{
+        classes::{PlayerClasses, SelectedClass},
+        components::{GgrsConfig, LevelSeed, LocalPlayerHandle, PlayerNames},
+        fnv::Fnv64,
+        loot::LootTables,
+        protocol::{
+            broadcast, data_hash, Handshake, LobbyPeer, PeerMessage, StartMatch, GAME_VERSION,
+            GGRS_CHANNEL, RELIABLE_CHANNEL,
+        },
+        spectator::SpectatorCamera,
+    },
+    DataAssets, FontAssets, GameState,
+};
+
+use super::components::{Lobby, LobbyText, PeerListText};
+
+/**
+ * Seconds between everyone being ready and the match starting.
+ */
+pub const LOBBY_COUNTDOWN_SECONDS: f32 = 3.0;
+
+#[allow(clippy::too_many_arguments)]
+pub fn setup(
+    mut commands: Commands,
+    fonts: Res<FontAssets>,
+    mut socket: ResMut<MatchboxSocket<MultipleChannels>>,
+    config: Res<MatchConfig>,
+    name: Res<PlayerName>,
+    selected_class: Res<SelectedClass>,
+    data: Res<DataAssets>,
+    loot_tables: Res<Assets<LootTables>>,
+) {
+    let loot = loot_tables.get(&data.loot).expect("loot tables not loaded");
+
+    // spectators have nothing to get ready
+    let local = LobbyPeer {
+        name: name.0.clone(),
+        class: (!config.spectate).then_some(selected_class.0),
+        ready: config.spectate,
+    };
+    broadcast(&mut socket, &PeerMessage::Lobby(local.clone()));
+    commands.insert_resource(Lobby {
+        local,
+        peers: default(),
+        countdown: None,
+        data_hash: data_hash(loot),
+        handshake: None,
+        handshakes: default(),
+        refusal: None,
+        start: None,
+    });
+
+    let help = if config.spectate {
+        "Waiting for the players..."
+    } else {
+        "Space to toggle ready\n< > to change class"
+    };
+    commands.spawn((
+        TextBundle::from_sections([TextSection::new(
+            help,
+            TextStyle {
+                font: fonts.visitor.clone(),
+                font_size: 24.0,
+                color: Color::WHITE,
+            },
+        )])
+        .with_style(Style {
+            margin: UiRect {
+                top: Val::Px(32.0),
+                right: Val::Auto,
+                left: Val::Auto,
+                ..default()
+            },
+            ..default()
+        }),
+        LobbyText,
+    ));
+    commands.spawn((
+        TextBundle::from_sections([TextSection::new(
+            "",
+            TextStyle {
+                font: fonts.visitor.clone(),
+                font_size: 24.0,
+                color: Color::WHITE,
+            },
+        )])
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                top: Val::Percent(30.0),
+                ..default()
+            },
+            margin: UiRect {
+                left: Val::Auto,
+                right: Val::Auto,
+                ..default()
+            },
+            ..default()
+        }),
+        PeerListText,
+        LobbyText,
+    ));
+}
+
+/**
+ * Greets peers joining the lobby and keeps track of what everyone has told us.
+ */
+pub fn sync_lobby(mut socket: ResMut<MatchboxSocket<MultipleChannels>>, mut lobby: ResMut<Lobby>) {
+    for (peer, state) in socket.update_peers() {
+        match state {
+            PeerState::Connected => {
+                let hello = PeerMessage::Lobby(lobby.local.clone());
+                socket
+                    .channel(RELIABLE_CHANNEL)
+                    .send(hello.to_packet(), peer);
+                if let Some(handshake) = lobby.handshake.clone() {
+                    let handshake = PeerMessage::Handshake(handshake);
+                    socket
+                        .channel(RELIABLE_CHANNEL)
+                        .send(handshake.to_packet(), peer);
+                }
+            }
+            PeerState::Disconnected => {
+                info!("{:?} left the lobby", peer);
+                lobby.peers.remove(&peer);
+                lobby.handshakes.remove(&peer);
+            }
+        }
+    }
+
+    for (peer, packet) in socket.channel(RELIABLE_CHANNEL).receive() {
+        match PeerMessage::from_packet(&packet) {
+            Some(PeerMessage::Lobby(info)) => {
+                lobby.peers.insert(peer, info);
+            }
+            Some(PeerMessage::Handshake(handshake)) => {
+                lobby.handshakes.insert(peer, handshake);
+            }
+            Some(PeerMessage::Start(start)) => {
+                lobby.start = Some((peer, start));
+            }
+            Some(message) => warn!("Unexpected message from {:?}: {:?}", peer, message),
+            None => warn!("Received an unknown message from {:?}", peer),
+        }
+    }
+
+    // the seed depends on who's playing, so it's sent again whenever someone comes or goes
+    let Some(our_id) = socket.id() else {
+        return;
+    };
+    let handshake = Handshake {
+        version: GAME_VERSION.into(),
+        data_hash: lobby.data_hash,
+        seed: level_seed(our_id, &lobby),
+    };
+    if lobby.handshake.as_ref() != Some(&handshake) {
+        broadcast(&mut socket, &PeerMessage::Handshake(handshake.clone()));
+        lobby.handshake = Some(handshake);
+    }
+}
+
+pub fn lobby_controls(
+    keyboard: Res<Input<KeyCode>>,
+    chat: Res<ChatInput>,
+    mut socket: ResMut<MatchboxSocket<MultipleChannels>>,
+    mut lobby: ResMut<Lobby>,
+    mut selected_class: ResMut<SelectedClass>,
+) {
+    if chat.typing {
+        return;
+    }
+    let Some(class) = lobby.local.class else {
+        return; // spectating
+    };
+
+    if keyboard.just_pressed(KeyCode::Space) {
+        lobby.local.ready = !lobby.local.ready;
+        lobby.refusal = None;
+    } else if lobby.local.ready {
+        return; // the class is locked in while ready
+    } else if keyboard.just_pressed(KeyCode::Left) {
+        selected_class.0 = class.previous();
+        lobby.local.class = Some(selected_class.0);
+    } else if keyboard.just_pressed(KeyCode::Right) {
+        selected_class.0 = class.next();
+        lobby.local.class = Some(selected_class.0);
+    } else {
+        return;
+    }
+
+    broadcast(&mut socket, &PeerMessage::Lobby(lobby.local.clone()));
+}
+
+pub fn countdown(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut socket: ResMut<MatchboxSocket<MultipleChannels>>,
+    config: Res<MatchConfig>,
+    mut lobby: ResMut<Lobby>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let Some(our_id) = socket.id() else {
+        return;
+    };
+
+    // the first player decides who plays, whatever our own countdown says
+    if let Some((host, start)) = lobby.start.take() {
+        let first_player = start.players().next().map(|(peer, _)| *peer);
+        if first_player != Some(host) {
+            warn!(
+                "{:?} tried to start the match without being the first player",
+                host
+            );
+        } else if !start.roster.iter().any(|(peer, _)| *peer == our_id) {
+            warn!("{:?} started a match without us", host);
+        } else {
+            info!("{:?} started the match, going in-game", host);
+            start_session(&mut commands, &mut socket, our_id, &start);
+            next_state.set(GameState::InGame);
+            return;
+        }
+    }
+
+    // a room of spectators has nobody to play the match
+    if !lobby.everyone_ready(config.peers())
+        || lobby.incompatibility().is_some()
+        || !lobby.has_players()
+        || lobby.player_count_mismatch(config.players).is_some()
+    {
+        lobby.countdown = None;
+        return;
+    }
+
+    let countdown = lobby
+        .countdown
+        .get_or_insert_with(|| Timer::from_seconds(LOBBY_COUNTDOWN_SECONDS, TimerMode::Once));
+    if !countdown.tick(time.delta()).finished() {
+        return;
+    }
+
+    if let Some(refusal) = lobby.seed_disagreement() {
+        warn!("Not starting the match: {}", refusal);
+        lobby.countdown = None;
+        lobby.refusal = Some(refusal);
+        if lobby.local.class.is_some() {
+            lobby.local.ready = false;
+            broadcast(&mut socket, &PeerMessage::Lobby(lobby.local.clone()));
+        }
+        return;
+    }
+
+    let roster: Vec<_> = sorted_peers(our_id, &lobby)
+        .into_iter()
+        .map(|(id, peer)| (id, peer.clone()))
+        .collect();
+    let start = StartMatch {
+        roster,
+        seed: level_seed(our_id, &lobby),
+    };
+    if start.players().next().map(|(peer, _)| *peer) != Some(our_id) {
+        return; // waiting for the first player to start it
+    }
+
+    info!("Everyone is ready, going in-game");
+    broadcast(&mut socket, &PeerMessage::Start(start.clone()));
+    start_session(&mut commands, &mut socket, our_id, &start);
+    next_state.set(GameState::InGame);
+}
+
+/**
+ * Everyone in the lobby including us, in the same order for every peer.
+ */
+fn sorted_peers(our_id: PeerId, lobby: &Lobby) -> Vec<(PeerId, &LobbyPeer)> {
+    let mut peers: Vec<_> = lobby
+        .peers
+        .iter()
+        .map(|(id, peer)| (*id, peer))
+        .chain(std::iter::once((our_id, &lobby.local)))
+        .collect();
+    peers.sort_by_key(|(id, _)| *id);
+    peers
+}
+
+/**
+ * Every peer knows the same players, so they all arrive at the same seed.
+ */
+fn level_seed(our_id: PeerId, lobby: &Lobby) -> u64 {
+    let mut hasher = Fnv64::new();
+    for (peer, info) in sorted_peers(our_id, lobby) {
+        if info.class.is_some() {
+            hasher.write(peer.0.as_bytes());
+        }
+    }
+    hasher.finish()
+}
+
+/**
+ * Builds the session the first player started, the same way on every peer.
+ */
+fn start_session(
+    commands: &mut Commands,
+    socket: &mut MatchboxSocket<MultipleChannels>,
+    our_id: PeerId,
+    start: &StartMatch,
+) {
+    let (players, spectators): (Vec<_>, Vec<_>) = start
+        .roster
+        .iter()
+        .partition(|(_, peer)| peer.class.is_some());
+    let num_players = players.len();
+
+    commands.insert_resource(PlayerClasses(
+        players.iter().filter_map(|(_, peer)| peer.class).collect(),
+    ));
+    commands.insert_resource(PlayerNames(
+        players.iter().map(|(_, peer)| peer.name.clone()).collect(),
+    ));
+
+    commands.insert_resource(LevelSeed(start.seed));
+
+    // move the channel out of the socket (required because GGRS takes ownership of it)
+    let channel = socket.take_channel(GGRS_CHANNEL).unwrap();
+
+    let session_builder = ggrs::SessionBuilder::<GgrsConfig>::new()
+        .with_num_players(num_players)
+        .with_input_delay(2);
+
+    if !players.iter().any(|(peer, _)| *peer == our_id) {
+        // spectators are fed by the first player
+        let host = players[0].0;
+        let ggrs_session = session_builder.start_spectator_session(host, channel);
+        commands.insert_resource(bevy_ggrs::Session::SpectatorSession(ggrs_session));
+        commands.init_resource::<SpectatorCamera>();
+        return;
+    }
+
+    // create a GGRS P2P session
+    let mut session_builder = session_builder;
+    for (handle, (peer, _)) in players.iter().enumerate() {
+        let player = if *peer == our_id {
+            commands.insert_resource(LocalPlayerHandle(handle));
+            PlayerType::Local
+        } else {
+            PlayerType::Remote(*peer)
+        };
+        session_builder = session_builder
+            .add_player(player, handle)
+            .expect("failed to add player");
+    }
+
+    // the first player sends the confirmed inputs on to every spectator
+    if players[0].0 == our_id {
+        for (i, (peer, _)) in spectators.into_iter().enumerate() {
+            session_builder = session_builder
+                .add_player(PlayerType::Spectator(*peer), num_players + i)
+                .expect("failed to add spectator");
+        }
+    }
+
+    // start the GGRS session
+    let ggrs_session = session_builder
+        .start_p2p_session(channel)
+        .expect("failed to start session");
+    commands.insert_resource(bevy_ggrs::Session::P2PSession(ggrs_session));
+}
+
+fn peer_label(peer: &LobbyPeer, you: bool) -> String {
+    let you = if you { " (you)" } else { "" };
+    match peer.class {
+        Some(class) => {
+            let ready = if peer.ready { "ready" } else { "not ready" };
+            format!("{}{} - {} - {}", peer.name, you, class.name(), ready)
+        }
+        None => format!("{}{} - spectating", peer.name, you),
+    }
+}
+
+pub fn update_peer_list(
+    socket: Res<MatchboxSocket<MultipleChannels>>,
+    config: Res<MatchConfig>,
+    lobby: Res<Lobby>,
+    mut texts: Query<&mut Text, With<PeerListText>>,
+) {
+    let Some(our_id) = socket.id() else {
+        return;
+    };
+
+    let mut label: Vec<_> = sorted_peers(our_id, &lobby)
+        .into_iter()
+        .map(|(id, peer)| peer_label(peer, id == our_id))
+        .collect();
+    label.push(String::new());
+    label.push(
+        match (lobby.incompatibility(), &lobby.refusal, &lobby.countdown) {
+            (Some(reason), _, _) => format!("Can't play together:\n{reason}"),
+            (None, _, Some(timer)) => format!("Starting in {}", timer.remaining_secs().ceil()),
+            (None, Some(refusal), None) => format!("Couldn't start: {refusal}"),
+            _ if lobby.peers.len() + 1 < config.peers() => "Waiting for players...".into(),
+            _ if !lobby.has_players() => "Nobody in the room is playing, can't start".into(),
+            _ => match lobby.player_count_mismatch(config.players) {
+                Some(mismatch) => format!("Can't start: {mismatch}"),
+                None => "Waiting for everyone to get ready...".into(),
+            },
+        },
+    );
+
+    for mut text in texts.iter_mut() {
+        text.sections[0].value = label.join("\n");
+    }
+}
+
+pub fn teardown(mut commands: Commands, texts: Query<(Entity, With<LobbyText>)>) {
+    for (entity, _) in texts.iter() {
+        commands.entity(entity).despawn();
+    }
+    commands.remove_resource::<Lobby>();
+}
@@ -4,22 +4,32 @@ use bevy::{
     input::common_conditions::input_toggle_active,
     log::{Level, LogPlugin},
     prelude::*,
-    window::PresentMode,
     DefaultPlugins,
 };
 use bevy_asset_loader::prelude::{AssetCollection, LoadingState, LoadingStateAppExt};
 use bevy_ecs_tilemap::prelude::*;
 use bevy_inspector_egui::quick::WorldInspectorPlugin;
-use bevy_matchbox::MatchboxSocket;
 use bevy_turborand::prelude::*;
-use config::Debug;
-use game::GamePlugin;
+use chat::ChatPlugin;
+use config::{Debug, GameMode, SignalingHost};
+use controls::{bindings::Bindings, ControlsPlugin};
+use game::{classes::SelectedClass, loot::LootTables, GamePlugin};
+use lobby::LobbyPlugin;
 use main_menu::*;
+use matchmaking::MatchmakingPlugin;
+use settings::{components::Settings, SettingsPlugin};
 use std::{env, process};
 
+mod chat;
 mod config;
+mod controls;
 mod game;
+mod lobby;
 mod main_menu;
+mod matchmaking;
+mod settings;
+#[cfg(not(target_arch = "wasm32"))]
+mod signaling;
 
 pub const SCREEN: Vec2 = Vec2::from_array([512.0, 512.0]);
 pub const DARK: Color = Color::rgb(0.191, 0.184, 0.156);
@@ -28,11 +38,20 @@ pub const LIGHT: Color = Color::rgb(0.852, 0.844, 0.816);
 
 #[derive(AssetCollection, Resource)]
 pub struct ImageAssets {
-    #[asset(texture_atlas(tile_size_x = 16.0, tile_size_y = 16.0, columns = 4, rows = 1))]
-    #[asset(path = "textures/chars/char_atlas.png")]
-    pub char_idle: Handle<TextureAtlas>,
+    #[asset(texture_atlas(tile_size_x = 16.0, tile_size_y = 16.0, columns = 4, rows = 4))]
+    #[asset(path = "textures/chars/warrior_atlas.png")]
+    pub warrior: Handle<TextureAtlas>,
+    #[asset(texture_atlas(tile_size_x = 16.0, tile_size_y = 16.0, columns = 4, rows = 4))]
+    #[asset(path = "textures/chars/ranger_atlas.png")]
+    pub ranger: Handle<TextureAtlas>,
+    #[asset(texture_atlas(tile_size_x = 16.0, tile_size_y = 16.0, columns = 4, rows = 4))]
+    #[asset(path = "textures/chars/mage_atlas.png")]
+    pub mage: Handle<TextureAtlas>,
     #[asset(path = "textures/atlas.png")]
     pub atlas: Handle<Image>,
+    #[asset(texture_atlas(tile_size_x = 16.0, tile_size_y = 16.0, columns = 23, rows = 33))]
+    #[asset(path = "textures/atlas.png")]
+    pub tiles: Handle<TextureAtlas>,
 }
 
 #[derive(AssetCollection, Resource)]
@@ -42,13 +61,21 @@ pub struct FontAssets {
     pub visitor: Handle<Font>,
 }
 
+#[derive(AssetCollection, Resource)]
+pub struct DataAssets {
+    #[asset(path = "data/drops.loot.ron")]
+    pub loot: Handle<LootTables>,
+}
+
 #[derive(States, Clone, Eq, PartialEq, Debug, Hash, Default)]
 pub enum GameState {
     MainMenu,
     #[default]
     AssetLoading,
     Matchmaking,
+    Lobby,
     InGame,
+    Controls,
 }
 
 fn main() {
@@ -59,15 +86,31 @@ fn main() {
         process::exit(1);
     });
 
+    // local games don't need anything from the main menu
+    let after_loading = match cfg.mode {
+        GameMode::Online => GameState::MainMenu,
+        GameMode::Solo | GameMode::SyncTest | GameMode::Replay => GameState::Matchmaking,
+    };
+
+    let settings = Settings::load();
+    let (width, height) = settings.window_size;
+
     let mut app = App::new();
+    if let Some(port) = cfg.host {
+        app.insert_resource(host(port));
+    }
+    if let Some(replay) = cfg.replay {
+        app.insert_resource(replay);
+    }
 
     app.add_plugins(
         DefaultPlugins
             .set(WindowPlugin {
                 primary_window: Some(Window {
                     title: "Dungeons of Redrod".into(),
-                    resolution: (SCREEN.x, SCREEN.y).into(),
-                    present_mode: PresentMode::AutoNoVsync,
+                    resolution: (width as f32, height as f32).into(),
+                    mode: settings.window_mode(),
+                    present_mode: settings.present_mode(),
                     // Tells wasm to resize the window according to the available canvas
                     fit_canvas_to_parent: true,
                     // Tells wasm not to override default event handling, like F5, Ctrl+R etc.
@@ -78,29 +121,56 @@ fn main() {
             })
             .set(LogPlugin {
                 level: Level::ERROR,
-                filter: "game=info".to_string(), //wgpu=error,bevy_render=info,bevy_ecs=trace
+                filter: "game=info,bevy_ggrs=warn".to_string(), //wgpu=error,bevy_render=info,bevy_ecs=trace
             })
             .set(ImagePlugin::default_nearest()),
     )
     .add_state::<GameState>()
-    .add_loading_state(
-        LoadingState::new(GameState::AssetLoading).continue_to_state(GameState::Matchmaking),
-    )
+    .add_loading_state(LoadingState::new(GameState::AssetLoading).continue_to_state(after_loading))
     .add_collection_to_loading_state::<_, ImageAssets>(GameState::AssetLoading)
     .add_collection_to_loading_state::<_, FontAssets>(GameState::AssetLoading)
+    .add_collection_to_loading_state::<_, DataAssets>(GameState::AssetLoading)
     .insert_resource(Debug(cfg.debug))
+    .insert_resource(cfg.name)
+    .insert_resource(SelectedClass(cfg.class))
+    .insert_resource(cfg.mode)
+    .insert_resource(cfg.check_distance)
+    .insert_resource(cfg.matchmaking)
+    .insert_resource(Bindings::load())
+    .insert_resource(settings)
     .add_plugin(TilemapPlugin)
     .add_plugin(FrameTimeDiagnosticsPlugin::default())
-    .add_plugin(WorldInspectorPlugin::default().run_if(input_toggle_active(false, KeyCode::Escape)))
+    .add_plugin(WorldInspectorPlugin::default().run_if(input_toggle_active(false, KeyCode::F12)))
     .add_plugin(RngPlugin::default())
     .add_plugin(MainMenuPlugin)
+    .add_plugin(ControlsPlugin)
+    .add_plugin(SettingsPlugin)
+    .add_plugin(MatchmakingPlugin)
+    .add_plugin(LobbyPlugin)
+    .add_plugin(ChatPlugin)
     .add_plugin(GamePlugin)
-    // todo: consider moving this to a seperate plugin "MatchmakingPlugin"
-    .add_systems((setup, start_matchbox_socket).in_schedule(OnEnter(GameState::Matchmaking)));
+    .add_startup_system(setup);
 
     app.run();
 }
 
+/**
+ * Starts the signaling server the other players join through.
+ */
+#[cfg(not(target_arch = "wasm32"))]
+fn host(port: u16) -> SignalingHost {
+    signaling::start_signaling_server(port).unwrap_or_else(|err| {
+        println!("Could not host on port {port}: {err}");
+        process::exit(1);
+    })
+}
+
+#[cfg(target_arch = "wasm32")]
+fn host(_: u16) -> SignalingHost {
+    println!("Hosting isn't possible in the browser");
+    process::exit(1);
+}
+
 fn setup(mut commands: Commands) {
     commands.spawn(Camera2dBundle {
         camera_2d: Camera2d {
@@ -109,9 +179,3 @@ fn setup(mut commands: Commands) {
         ..default()
     });
 }
-
-fn start_matchbox_socket(mut commands: Commands) {
-    let room_url = "ws://127.0.0.1:3536/dungeons_of_redrod?next=2";
-    info!("connecting to matchbox server: {:?}", room_url);
-    commands.insert_resource(MatchboxSocket::new_ggrs(room_url));
-}
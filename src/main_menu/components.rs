@@ -1,4 +1,137 @@
-use bevy::prelude::Component;
+use bevy::prelude::{Component, Resource};
+
+use crate::settings::components::SettingItem;
+
+/**
+ * Root of the current menu page, despawned along with everything on it.
+ */
+#[derive(Component)]
+pub struct MainMenuText(pub MenuPage);
 
 #[derive(Component)]
-pub struct MainMenuText;
+pub struct MenuButton(pub MenuItem);
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum MenuPage {
+    #[default]
+    Main,
+    Join,
+    Settings,
+    Display,
+    Audio,
+}
+
+impl MenuPage {
+    pub fn items(&self) -> &'static [MenuItem] {
+        match self {
+            MenuPage::Main => &[
+                MenuItem::Host,
+                MenuItem::Join,
+                MenuItem::Solo,
+                MenuItem::Settings,
+                MenuItem::Quit,
+            ],
+            MenuPage::Join => &[
+                MenuItem::RoomCode,
+                MenuItem::HostAddress,
+                MenuItem::Connect,
+                MenuItem::Back,
+            ],
+            MenuPage::Settings => &[
+                MenuItem::Class,
+                MenuItem::Players,
+                MenuItem::Display,
+                MenuItem::Audio,
+                MenuItem::Controls,
+                MenuItem::Back,
+            ],
+            MenuPage::Display => &[
+                MenuItem::Setting(SettingItem::WindowSize),
+                MenuItem::Setting(SettingItem::Fullscreen),
+                MenuItem::Setting(SettingItem::Vsync),
+                MenuItem::Setting(SettingItem::PixelScale),
+                MenuItem::Back,
+            ],
+            MenuPage::Audio => &[
+                MenuItem::Setting(SettingItem::MasterVolume),
+                MenuItem::Setting(SettingItem::MusicVolume),
+                MenuItem::Setting(SettingItem::EffectsVolume),
+                MenuItem::Back,
+            ],
+        }
+    }
+
+    /**
+     * The page back leads to.
+     */
+    pub fn parent(&self) -> MenuPage {
+        match self {
+            MenuPage::Display | MenuPage::Audio => MenuPage::Settings,
+            _ => MenuPage::Main,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MenuItem {
+    Host,
+    Join,
+    Solo,
+    Settings,
+    Quit,
+    RoomCode,
+    HostAddress,
+    Connect,
+    Class,
+    Players,
+    Controls,
+    Display,
+    Audio,
+    Setting(SettingItem),
+    Back,
+}
+
+/**
+ * Where in the menu we are, kept around so leaving the controls returns to the settings.
+ */
+#[derive(Resource, Default)]
+pub struct Menu {
+    pub page: MenuPage,
+    pub selected: usize,
+    /// The room code typed in on the join page.
+    pub code: String,
+    /// Address of the player hosting, typed in on the join page when joining over the LAN.
+    pub address: String,
+    /// The server set before joining a host, put back when hosting ourselves.
+    pub server: Option<String>,
+}
+
+impl Menu {
+    pub fn selected_item(&self) -> MenuItem {
+        self.page.items()[self.selected]
+    }
+
+    /**
+     * The text typing goes to on the join page, the address when it's selected, the code otherwise.
+     */
+    pub fn typed_text(&mut self) -> &mut String {
+        match self.selected_item() {
+            MenuItem::HostAddress => &mut self.address,
+            _ => &mut self.code,
+        }
+    }
+
+    pub fn open(&mut self, page: MenuPage) {
+        self.page = page;
+        self.selected = 0;
+    }
+}
+
+/**
+ * Something done to a menu item, by keyboard or mouse.
+ */
+pub enum MenuAction {
+    Activate(MenuItem),
+    /// Steps a setting back or forth.
+    Adjust(MenuItem, i32),
+}
@@ -1,17 +1,36 @@
-use self::systems::{setup, teardown, transition_to_game};
+use self::{
+    components::{Menu, MenuAction},
+    systems::{
+        mouse_menu, navigate_menu, refresh_page, run_menu_actions, setup, teardown, type_join_text,
+        update_menu_labels,
+    },
+};
 use crate::GameState;
 use bevy::prelude::*;
 mod components;
 mod systems;
+
 /**
- * sett state for lukking
- * hello world fader ut
+ * The first thing online players see, from here they host, join or play on their own.
  */
 pub struct MainMenuPlugin;
 impl Plugin for MainMenuPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system(setup.in_schedule(OnEnter(GameState::MainMenu)))
-            .add_system(transition_to_game.in_set(OnUpdate(GameState::MainMenu)))
+        app.init_resource::<Menu>()
+            .add_event::<MenuAction>()
+            .add_system(setup.in_schedule(OnEnter(GameState::MainMenu)))
+            .add_systems(
+                (
+                    navigate_menu,
+                    mouse_menu,
+                    type_join_text,
+                    run_menu_actions,
+                    refresh_page,
+                    update_menu_labels,
+                )
+                    .chain()
+                    .in_set(OnUpdate(GameState::MainMenu)),
+            )
             .add_system(teardown.in_schedule(OnExit(GameState::MainMenu)));
     }
 }
@@ -1,71 +1,416 @@
-use bevy::prelude::*;
+use bevy::{app::AppExit, prelude::*};
+use bevy_turborand::{DelegatedRng, GlobalRng};
 
-use crate::{FontAssets, GameState};
+use crate::{
+    config::{host_server, GameMode, MatchConfig, SignalingHost, MAX_PLAYERS, MIN_PLAYERS},
+    game::classes::SelectedClass,
+    settings::components::Settings,
+    FontAssets, GameState, DARK, LIGHT,
+};
 
-use super::components::MainMenuText;
+use super::components::{MainMenuText, Menu, MenuAction, MenuButton, MenuItem, MenuPage};
 
-pub fn transition_to_game(
-    mut next_state: ResMut<NextState<GameState>>,
-    keyboard: Res<Input<KeyCode>>,
+/**
+ * Letters room codes are made of, leaving out the ones easily mistaken for digits.
+ */
+pub const ROOM_CODE_LETTERS: &[char] = &[
+    'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'J', 'K', 'L', 'M', 'N', 'P', 'Q', 'R', 'S', 'T', 'U',
+    'V', 'W', 'X', 'Y', 'Z',
+];
+pub const ROOM_CODE_LENGTH: usize = 4;
+pub const MAX_ROOM_CODE_LENGTH: usize = 32;
+pub const MAX_HOST_ADDRESS_LENGTH: usize = 64;
+
+/**
+ * A fresh room code, ending in the number of players so whoever joins waits for as many.
+ */
+fn new_room_code(rng: &mut GlobalRng, players: usize) -> String {
+    let mut code: String = (0..ROOM_CODE_LENGTH)
+        .filter_map(|_| rng.sample(ROOM_CODE_LETTERS).copied())
+        .collect();
+    code.push_str(&players.to_string());
+    code
+}
+
+fn players_in_code(code: &str) -> Option<usize> {
+    let players = code.chars().last()?.to_digit(10)? as usize;
+    (MIN_PLAYERS..=MAX_PLAYERS)
+        .contains(&players)
+        .then_some(players)
+}
+
+/**
+ * Runs the signaling server in this process when the room is on the default local server,
+ * unless something else is listening there already.
+ */
+#[cfg(not(target_arch = "wasm32"))]
+fn start_local_server(
+    commands: &mut Commands,
+    config: &MatchConfig,
+    hosting: Option<&SignalingHost>,
 ) {
-    if keyboard.pressed(KeyCode::Space) {
-        next_state.set(GameState::InGame);
+    if hosting.is_some() || config.server != MatchConfig::default().server {
+        return;
+    }
+    match crate::signaling::start_signaling_server(crate::config::DEFAULT_SIGNALING_PORT) {
+        Ok(host) => commands.insert_resource(host),
+        Err(err) => warn!(
+            "Not hosting, using the server at {}: {}",
+            config.server, err
+        ),
     }
 }
 
-pub fn setup(mut commands: Commands, fonts: Res<FontAssets>) {
-    // Text with multiple sections
-    commands.spawn((
-        // Create a TextBundle that has a Text with a list of sections.
-        TextBundle::from_sections([TextSection::new(
-            "GAME TITLE!",
-            TextStyle {
-                font: fonts.visitor.clone(),
-                font_size: 40.0,
-                color: Color::WHITE,
-            },
-        )])
-        .with_style(Style {
-            margin: UiRect {
-                top: Val::Px(32.0),
-                right: Val::Auto,
-                left: Val::Auto,
-                ..default()
-            },
+#[cfg(target_arch = "wasm32")]
+fn start_local_server(_: &mut Commands, _: &MatchConfig, _: Option<&SignalingHost>) {}
 
-            ..default()
-        }),
-        MainMenuText,
-    ));
-    commands.spawn((
-        // Create a TextBundle that has a Text with a list of sections.
-        TextBundle::from_sections([TextSection::new(
-            "Press space to continue...",
-            TextStyle {
-                font: fonts.visitor.clone(),
-                font_size: 24.0,
-                color: Color::WHITE,
-            },
-        )])
-        .with_style(Style {
-            position_type: PositionType::Absolute,
-            position: UiRect {
-                top: Val::Percent(30.0),
-                ..default()
-            },
-            margin: UiRect {
-                left: Val::Auto,
-                right: Val::Auto,
+fn item_label(
+    item: MenuItem,
+    menu: &Menu,
+    class: &SelectedClass,
+    config: &MatchConfig,
+    settings: &Settings,
+) -> String {
+    match item {
+        MenuItem::Host => "Host".into(),
+        MenuItem::Join => "Join".into(),
+        MenuItem::Solo => "Solo".into(),
+        MenuItem::Settings => "Settings".into(),
+        MenuItem::Quit => "Quit".into(),
+        MenuItem::RoomCode => format!("Code: {}_", menu.code),
+        MenuItem::HostAddress => format!("Host: {}_", menu.address),
+        MenuItem::Connect => "Connect".into(),
+        MenuItem::Class => format!("< {} >", class.0.name()),
+        MenuItem::Players => format!("< Players: {} >", config.players),
+        MenuItem::Controls => "Controls".into(),
+        MenuItem::Display => "Display".into(),
+        MenuItem::Audio => "Audio".into(),
+        MenuItem::Setting(setting) => settings.label(setting),
+        MenuItem::Back => "Back".into(),
+    }
+}
+
+fn page_help(page: MenuPage) -> &'static str {
+    match page {
+        MenuPage::Main => "Arrow keys and enter, or the mouse",
+        MenuPage::Join => "Type the room code, and the host's address on a LAN",
+        MenuPage::Settings | MenuPage::Display | MenuPage::Audio => {
+            "Left and right change a setting"
+        }
+    }
+}
+
+fn spawn_page(commands: &mut Commands, fonts: &FontAssets, page: MenuPage) {
+    let text_style = |font_size| TextStyle {
+        font: fonts.visitor.clone(),
+        font_size,
+        color: Color::WHITE,
+    };
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    size: Size::width(Val::Percent(100.0)),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
                 ..default()
             },
-            ..default()
-        }),
-        MainMenuText,
-    ));
+            MainMenuText(page),
+        ))
+        .with_children(|parent| {
+            parent.spawn(
+                TextBundle::from_section("Dungeons of Redrod", text_style(40.0)).with_style(
+                    Style {
+                        margin: UiRect::vertical(Val::Px(32.0)),
+                        ..default()
+                    },
+                ),
+            );
+            for item in page.items() {
+                parent
+                    .spawn((
+                        ButtonBundle {
+                            style: Style {
+                                size: Size::new(Val::Px(320.0), Val::Px(36.0)),
+                                justify_content: JustifyContent::Center,
+                                align_items: AlignItems::Center,
+                                margin: UiRect::all(Val::Px(4.0)),
+                                ..default()
+                            },
+                            background_color: Color::NONE.into(),
+                            ..default()
+                        },
+                        MenuButton(*item),
+                    ))
+                    .with_children(|button| {
+                        button.spawn(TextBundle::from_section("", text_style(24.0)));
+                    });
+            }
+            parent.spawn(
+                TextBundle::from_section(page_help(page), text_style(16.0)).with_style(Style {
+                    margin: UiRect::top(Val::Px(32.0)),
+                    ..default()
+                }),
+            );
+        });
+}
+
+pub fn setup(mut commands: Commands, fonts: Res<FontAssets>, menu: Res<Menu>) {
+    spawn_page(&mut commands, &fonts, menu.page);
+}
+
+/**
+ * Up and down pick an item, enter picks it, left and right change settings.
+ */
+pub fn navigate_menu(
+    keyboard: Res<Input<KeyCode>>,
+    mut menu: ResMut<Menu>,
+    mut actions: EventWriter<MenuAction>,
+) {
+    let items = menu.page.items().len();
+    let item = menu.selected_item();
+    // on the join page backspace erases what was typed first
+    let go_back = match menu.page {
+        MenuPage::Main => false,
+        MenuPage::Join => menu.typed_text().is_empty(),
+        MenuPage::Settings | MenuPage::Display | MenuPage::Audio => true,
+    };
+    if keyboard.just_pressed(KeyCode::Up) {
+        menu.selected = menu.selected.checked_sub(1).unwrap_or(items - 1);
+    } else if keyboard.just_pressed(KeyCode::Down) {
+        menu.selected = (menu.selected + 1) % items;
+    } else if keyboard.any_just_pressed([KeyCode::Return, KeyCode::Space]) {
+        actions.send(MenuAction::Activate(item));
+    } else if keyboard.just_pressed(KeyCode::Left) {
+        actions.send(MenuAction::Adjust(item, -1));
+    } else if keyboard.just_pressed(KeyCode::Right) {
+        actions.send(MenuAction::Adjust(item, 1));
+    } else if keyboard.just_pressed(KeyCode::Back) && go_back {
+        actions.send(MenuAction::Activate(MenuItem::Back));
+    }
+}
+
+/**
+ * Hovering over an item selects it, clicking picks it.
+ */
+pub fn mouse_menu(
+    buttons: Query<(&Interaction, &MenuButton), Changed<Interaction>>,
+    mut menu: ResMut<Menu>,
+    mut actions: EventWriter<MenuAction>,
+) {
+    for (interaction, button) in buttons.iter() {
+        if *interaction == Interaction::None {
+            continue;
+        }
+        if let Some(i) = menu.page.items().iter().position(|item| *item == button.0) {
+            menu.selected = i;
+        }
+        if *interaction == Interaction::Clicked {
+            actions.send(MenuAction::Activate(button.0));
+        }
+    }
 }
 
-pub fn teardown(mut commands: Commands, texts: Query<(Entity, With<MainMenuText>)>) {
-    for (entity, _) in texts.iter() {
-        commands.entity(entity).despawn();
+/**
+ * Codes only take letters, digits, `-` and `_`, letters are capitalized like generated codes.
+ * Host addresses take letters, digits, `-`, `.` and `:` for the port.
+ */
+pub fn type_join_text(
+    keyboard: Res<Input<KeyCode>>,
+    mut characters: EventReader<ReceivedCharacter>,
+    mut menu: ResMut<Menu>,
+) {
+    let typed: Vec<_> = characters.iter().map(|event| event.char).collect();
+    if menu.page != MenuPage::Join {
+        return;
+    }
+
+    let address = menu.selected_item() == MenuItem::HostAddress;
+    let text = menu.typed_text();
+    for char in typed {
+        if address {
+            if (char.is_ascii_alphanumeric() || matches!(char, '-' | '.' | ':'))
+                && text.len() < MAX_HOST_ADDRESS_LENGTH
+            {
+                text.push(char);
+            }
+        } else if (char.is_ascii_alphanumeric() || matches!(char, '-' | '_'))
+            && text.len() < MAX_ROOM_CODE_LENGTH
+        {
+            text.push(char.to_ascii_uppercase());
+        }
+    }
+    if keyboard.just_pressed(KeyCode::Back) {
+        text.pop();
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn run_menu_actions(
+    mut commands: Commands,
+    mut actions: EventReader<MenuAction>,
+    mut menu: ResMut<Menu>,
+    mut config: ResMut<MatchConfig>,
+    mut mode: ResMut<GameMode>,
+    mut class: ResMut<SelectedClass>,
+    mut settings: ResMut<Settings>,
+    mut global_rng: ResMut<GlobalRng>,
+    hosting: Option<Res<SignalingHost>>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    for action in actions.iter() {
+        let (item, step) = match action {
+            MenuAction::Activate(item) => (*item, None),
+            MenuAction::Adjust(item, step) => (*item, Some(*step)),
+        };
+
+        match (item, step) {
+            (MenuItem::Host, None) => {
+                if let Some(server) = menu.server.take() {
+                    config.server = server;
+                }
+                config.room = new_room_code(&mut global_rng, config.players);
+                start_local_server(&mut commands, &config, hosting.as_deref());
+                *mode = GameMode::Online;
+                menu.open(MenuPage::Main);
+                next_state.set(GameState::Matchmaking);
+            }
+            (MenuItem::Join, None) => {
+                menu.code.clear();
+                menu.open(MenuPage::Join);
+            }
+            (MenuItem::RoomCode | MenuItem::HostAddress | MenuItem::Connect, None) => {
+                if menu.code.is_empty() {
+                    continue;
+                }
+                if let Some(server) = menu.server.take() {
+                    config.server = server;
+                }
+                if !menu.address.is_empty() {
+                    menu.server = Some(config.server.clone());
+                    config.server = host_server(&menu.address);
+                }
+                config.room = menu.code.clone();
+                if let Some(players) = players_in_code(&menu.code) {
+                    config.players = players;
+                }
+                *mode = GameMode::Online;
+                menu.open(MenuPage::Main);
+                next_state.set(GameState::Matchmaking);
+            }
+            (MenuItem::Solo, None) => {
+                *mode = GameMode::Solo;
+                next_state.set(GameState::Matchmaking);
+            }
+            (MenuItem::Settings, None) => menu.open(MenuPage::Settings),
+            (MenuItem::Quit, None) => exit.send(AppExit),
+            (MenuItem::Class, step) => {
+                class.0 = match step {
+                    Some(step) if step < 0 => class.0.previous(),
+                    _ => class.0.next(),
+                };
+            }
+            (MenuItem::Players, step) => {
+                // clicking cycles through the counts, the arrow keys stop at either end
+                config.players = match step {
+                    Some(step) => (config.players as i32 + step)
+                        .clamp(MIN_PLAYERS as i32, MAX_PLAYERS as i32)
+                        as usize,
+                    None if config.players >= MAX_PLAYERS => MIN_PLAYERS,
+                    None => config.players + 1,
+                };
+            }
+            (MenuItem::Controls, None) => next_state.set(GameState::Controls),
+            (MenuItem::Display, None) => menu.open(MenuPage::Display),
+            (MenuItem::Audio, None) => menu.open(MenuPage::Audio),
+            (MenuItem::Setting(setting), step) => settings.adjust(setting, step),
+            (MenuItem::Back, None) => {
+                let parent = menu.page.parent();
+                menu.open(parent);
+            }
+            (_, Some(_)) => {}
+        }
+    }
+}
+
+/**
+ * Swaps the page on screen for the one the menu is on.
+ */
+pub fn refresh_page(
+    mut commands: Commands,
+    fonts: Res<FontAssets>,
+    menu: Res<Menu>,
+    roots: Query<(Entity, &MainMenuText)>,
+) {
+    if roots.iter().all(|(_, root)| root.0 == menu.page) {
+        return;
+    }
+    for (entity, _) in roots.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    spawn_page(&mut commands, &fonts, menu.page);
+}
+
+pub fn update_menu_labels(
+    menu: Res<Menu>,
+    class: Res<SelectedClass>,
+    config: Res<MatchConfig>,
+    settings: Res<Settings>,
+    new_buttons: Query<(), Added<MenuButton>>,
+    mut buttons: Query<(&MenuButton, &mut BackgroundColor, &Children)>,
+    mut texts: Query<&mut Text>,
+) {
+    if !menu.is_changed()
+        && !class.is_changed()
+        && !config.is_changed()
+        && !settings.is_changed()
+        && new_buttons.is_empty()
+    {
+        return;
+    }
+
+    let selected = menu.selected_item();
+    for (button, mut background, children) in buttons.iter_mut() {
+        let (background_color, text_color) = if button.0 == selected {
+            (LIGHT, DARK)
+        } else {
+            (Color::NONE, Color::WHITE)
+        };
+        *background = background_color.into();
+        for child in children.iter() {
+            if let Ok(mut text) = texts.get_mut(*child) {
+                text.sections[0].value = item_label(button.0, &menu, &class, &config, &settings);
+                text.sections[0].style.color = text_color;
+            }
+        }
+    }
+}
+
+pub fn teardown(mut commands: Commands, roots: Query<(Entity, With<MainMenuText>)>) {
+    for (entity, _) in roots.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn player_count_from_the_last_digit() {
+        assert_eq!(players_in_code("ABCD2"), Some(2));
+        assert_eq!(players_in_code("ABCD4"), Some(4));
+    }
+
+    #[test]
+    fn no_player_count() {
+        assert_eq!(players_in_code(""), None);
+        assert_eq!(players_in_code("ABCD"), None);
+        assert_eq!(players_in_code("ABCD1"), None);
+        assert_eq!(players_in_code("ABCD5"), None);
     }
 }
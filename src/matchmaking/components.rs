@@ -0,0 +1,7 @@
+use bevy::prelude::Component;
+
+#[derive(Component)]
+pub struct MatchmakingText;
+
+#[derive(Component)]
+pub struct StatusText;
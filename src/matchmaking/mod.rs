@@ -0,0 +1,37 @@
+use self::systems::{
+    setup, start_local_session, start_matchbox_socket, teardown, update_status, wait_for_players,
+};
+use crate::{config::GameMode, GameState};
+use bevy::prelude::*;
+mod components;
+mod systems;
+
+/**
+ * Connects to the signaling server and waits for the room to fill up before starting the match,
+ * or starts a local session right away when playing solo or running a sync test.
+ */
+pub struct MatchmakingPlugin;
+impl Plugin for MatchmakingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            (
+                setup.run_if(resource_equals(GameMode::Online)),
+                start_matchbox_socket.run_if(resource_equals(GameMode::Online)),
+            )
+                .in_schedule(OnEnter(GameState::Matchmaking)),
+        )
+        .add_system(
+            start_local_session
+                .run_if(not(resource_equals(GameMode::Online)))
+                .in_schedule(OnEnter(GameState::Matchmaking)),
+        )
+        .add_systems(
+            (
+                wait_for_players.run_if(resource_equals(GameMode::Online)),
+                update_status.run_if(resource_equals(GameMode::Online)),
+            )
+                .in_set(OnUpdate(GameState::Matchmaking)),
+        )
+        .add_system(teardown.in_schedule(OnExit(GameState::Matchmaking)));
+    }
+}
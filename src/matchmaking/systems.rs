@@ -0,0 +1,207 @@
+use bevy::prelude::*;
+use bevy_ggrs::ggrs::{self, PlayerType};
+use bevy_matchbox::{
+    prelude::{MultipleChannels, WebRtcSocketBuilder},
+    MatchboxSocket,
+};
+use bevy_turborand::{DelegatedRng, GlobalRng};
+
+use crate::{
+    config::{CheckDistance, GameMode, MatchConfig, PlayerName, ReplayPath, SignalingHost},
+    game::{
+        classes::{PlayerClasses, SelectedClass},
+        components::{GgrsConfig, LevelSeed, LocalPlayerHandle, PlayerNames},
+        loot::LootTables,
+        protocol::data_hash,
+        replay::{Replay, ReplayPlayback},
+        spectator::SpectatorCamera,
+    },
+    DataAssets, FontAssets, GameState,
+};
+
+use super::components::{MatchmakingText, StatusText};
+
+pub fn start_matchbox_socket(mut commands: Commands, config: Res<MatchConfig>) {
+    let room_url = config.room_url();
+    info!("connecting to matchbox server: {:?}", room_url);
+    let socket = WebRtcSocketBuilder::new(room_url)
+        .add_ggrs_channel()
+        .add_reliable_channel()
+        .add_reliable_channel()
+        .build();
+    commands.insert_resource(MatchboxSocket::from(socket));
+}
+
+/**
+ * Waits for the room to fill up, the lobby takes it from there.
+ */
+pub fn wait_for_players(
+    mut socket: ResMut<MatchboxSocket<MultipleChannels>>,
+    config: Res<MatchConfig>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    // peers greeting us are greeted back by the lobby
+    socket.update_peers();
+
+    if socket.connected_peers().count() + 1 < config.peers() {
+        return; // wait for more players
+    }
+
+    info!("All peers have joined, going to the lobby");
+    next_state.set(GameState::Lobby);
+}
+
+/**
+ * Starts a session where every player is local, running the same rollback systems as online games.
+ * Solo games have a single player, sync tests have as many as an online match would and replays
+ * as many as were recorded.
+ */
+#[allow(clippy::too_many_arguments)]
+pub fn start_local_session(
+    mut commands: Commands,
+    mode: Res<GameMode>,
+    config: Res<MatchConfig>,
+    check_distance: Res<CheckDistance>,
+    name: Res<PlayerName>,
+    selected_class: Res<SelectedClass>,
+    replay_path: Option<Res<ReplayPath>>,
+    data: Res<DataAssets>,
+    loot_tables: Res<Assets<LootTables>>,
+    mut global_rng: ResMut<GlobalRng>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let replay = match (*mode, replay_path) {
+        (GameMode::Replay, Some(path)) => {
+            let loot = loot_tables.get(&data.loot).expect("loot tables not loaded");
+            let replay = Replay::load(&path.0).and_then(|replay| {
+                match replay.incompatibility(data_hash(loot)) {
+                    Some(reason) => Err(format!("{} was {}", path.0, reason)),
+                    None => Ok(replay),
+                }
+            });
+            match replay {
+                Ok(replay) => Some(replay),
+                Err(err) => {
+                    error!("Can't play the replay: {}", err);
+                    next_state.set(GameState::MainMenu);
+                    return;
+                }
+            }
+        }
+        _ => None,
+    };
+
+    // a sync test without any checks never rolls back, so it's just a local session
+    let (num_players, check_distance) = match (*mode, &replay) {
+        (GameMode::SyncTest, _) => (config.players, check_distance.0),
+        (_, Some(replay)) => (replay.num_players(), 0),
+        _ => (1, 0),
+    };
+
+    let mut session_builder = ggrs::SessionBuilder::<GgrsConfig>::new()
+        .with_num_players(num_players)
+        .with_check_distance(check_distance);
+    for handle in 0..num_players {
+        session_builder = session_builder
+            .add_player(PlayerType::Local, handle)
+            .expect("failed to add player");
+    }
+    let ggrs_session = session_builder
+        .start_synctest_session()
+        .expect("failed to start session");
+
+    match replay {
+        Some(replay) => {
+            commands.insert_resource(PlayerClasses(replay.classes.clone()));
+            commands.insert_resource(PlayerNames(replay.names.clone()));
+            commands.insert_resource(LevelSeed(replay.seed));
+            // nobody plays, the camera is free to follow anyone
+            commands.init_resource::<SpectatorCamera>();
+            commands.insert_resource(ReplayPlayback::new(replay));
+        }
+        None => {
+            commands.insert_resource(PlayerClasses(vec![selected_class.0; num_players]));
+            commands.insert_resource(PlayerNames(vec![name.0.clone(); num_players]));
+            commands.insert_resource(LevelSeed(global_rng.u64(..)));
+            commands.insert_resource(LocalPlayerHandle(0));
+        }
+    }
+    commands.insert_resource(bevy_ggrs::Session::SyncTestSession(ggrs_session));
+
+    info!("Starting a local game with {:?}", *mode);
+    next_state.set(GameState::InGame);
+}
+
+/**
+ * When hosting, the others on the LAN are told where to join instead of our own server url.
+ */
+fn status_label(connected: usize, config: &MatchConfig, hosting: Option<&SignalingHost>) -> String {
+    let role = if config.spectate {
+        "Waiting to spectate"
+    } else {
+        "Waiting for players"
+    };
+    let server = match hosting.and_then(SignalingHost::join_address) {
+        Some(address) => format!("host: {address}"),
+        None => format!("server: {}", config.server),
+    };
+    format!(
+        "{} {}/{}\nroom: {}\n{}",
+        role,
+        connected,
+        config.peers(),
+        config.room,
+        server
+    )
+}
+
+pub fn update_status(
+    socket: Res<MatchboxSocket<MultipleChannels>>,
+    config: Res<MatchConfig>,
+    hosting: Option<Res<SignalingHost>>,
+    mut texts: Query<&mut Text, With<StatusText>>,
+) {
+    let connected = socket.connected_peers().count() + 1;
+    for mut text in texts.iter_mut() {
+        text.sections[0].value = status_label(connected, &config, hosting.as_deref());
+    }
+}
+
+pub fn setup(
+    mut commands: Commands,
+    fonts: Res<FontAssets>,
+    config: Res<MatchConfig>,
+    hosting: Option<Res<SignalingHost>>,
+) {
+    commands.spawn((
+        TextBundle::from_sections([TextSection::new(
+            status_label(1, &config, hosting.as_deref()),
+            TextStyle {
+                font: fonts.visitor.clone(),
+                font_size: 24.0,
+                color: Color::WHITE,
+            },
+        )])
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                top: Val::Percent(30.0),
+                ..default()
+            },
+            margin: UiRect {
+                left: Val::Auto,
+                right: Val::Auto,
+                ..default()
+            },
+            ..default()
+        }),
+        StatusText,
+        MatchmakingText,
+    ));
+}
+
+pub fn teardown(mut commands: Commands, texts: Query<(Entity, With<MatchmakingText>)>) {
+    for (entity, _) in texts.iter() {
+        commands.entity(entity).despawn();
+    }
+}
@@ -0,0 +1,305 @@
+use bevy::{
+    prelude::*,
+    window::{PresentMode, WindowMode},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::SCREEN;
+
+/**
+ * The settings file, kept in the game's folder of the user's config directory.
+ */
+pub const SETTINGS_FILE: &str = "settings.ron";
+/**
+ * Window sizes the settings menu steps through.
+ */
+pub const WINDOW_SIZES: [(u32, u32); 5] = [
+    (512, 512),
+    (768, 768),
+    (1024, 1024),
+    (1280, 720),
+    (1920, 1080),
+];
+pub const MAX_PIXEL_SCALE: u32 = 4;
+/**
+ * Percent a volume changes by in the settings menu.
+ */
+pub const VOLUME_STEP: u32 = 10;
+
+/**
+ * How the game looks and sounds, saved whenever it changes.
+ */
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub window_size: (u32, u32),
+    pub fullscreen: bool,
+    pub vsync: bool,
+    /// Screen pixels per world unit.
+    pub pixel_scale: u32,
+    /// Volumes in percent, music and effects are scaled by the master volume.
+    pub master_volume: u32,
+    pub music_volume: u32,
+    pub effects_volume: u32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            window_size: (SCREEN.x as u32, SCREEN.y as u32),
+            fullscreen: false,
+            vsync: false,
+            pixel_scale: 1,
+            master_volume: 100,
+            music_volume: 100,
+            effects_volume: 100,
+        }
+    }
+}
+
+/**
+ * A setting the menus show and change, the main menu and the in-game one share them.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SettingItem {
+    WindowSize,
+    Fullscreen,
+    Vsync,
+    PixelScale,
+    MasterVolume,
+    MusicVolume,
+    EffectsVolume,
+}
+
+fn on_off(on: bool) -> &'static str {
+    if on {
+        "on"
+    } else {
+        "off"
+    }
+}
+
+/**
+ * Steps a volume in percent, clicking wraps around from full to silent.
+ */
+fn step_volume(volume: u32, step: Option<i32>) -> u32 {
+    match step {
+        Some(step) => (volume as i32 + step * VOLUME_STEP as i32).clamp(0, 100) as u32,
+        None if volume >= 100 => 0,
+        None => u32::min(volume + VOLUME_STEP, 100),
+    }
+}
+
+impl Settings {
+    pub fn label(&self, item: SettingItem) -> String {
+        let (width, height) = self.window_size;
+        match item {
+            SettingItem::WindowSize => format!("< Window: {width}x{height} >"),
+            SettingItem::Fullscreen => format!("Fullscreen: {}", on_off(self.fullscreen)),
+            SettingItem::Vsync => format!("VSync: {}", on_off(self.vsync)),
+            SettingItem::PixelScale => format!("< Pixel scale: {}x >", self.pixel_scale),
+            SettingItem::MasterVolume => format!("< Volume: {}% >", self.master_volume),
+            SettingItem::MusicVolume => format!("< Music: {}% >", self.music_volume),
+            SettingItem::EffectsVolume => format!("< Effects: {}% >", self.effects_volume),
+        }
+    }
+
+    /**
+     * Steps a setting back or forth, or to the next value when clicked without a step.
+     */
+    pub fn adjust(&mut self, item: SettingItem, step: Option<i32>) {
+        match item {
+            SettingItem::WindowSize => self.step_window_size(step.unwrap_or(1)),
+            SettingItem::Fullscreen => self.fullscreen = !self.fullscreen,
+            SettingItem::Vsync => self.vsync = !self.vsync,
+            SettingItem::PixelScale => {
+                self.pixel_scale = match step {
+                    Some(step) => {
+                        (self.pixel_scale as i32 + step).clamp(1, MAX_PIXEL_SCALE as i32) as u32
+                    }
+                    None => self.pixel_scale % MAX_PIXEL_SCALE + 1,
+                };
+            }
+            SettingItem::MasterVolume => self.master_volume = step_volume(self.master_volume, step),
+            SettingItem::MusicVolume => self.music_volume = step_volume(self.music_volume, step),
+            SettingItem::EffectsVolume => {
+                self.effects_volume = step_volume(self.effects_volume, step)
+            }
+        }
+    }
+
+    pub fn window_mode(&self) -> WindowMode {
+        if self.fullscreen {
+            WindowMode::BorderlessFullscreen
+        } else {
+            WindowMode::Windowed
+        }
+    }
+
+    pub fn present_mode(&self) -> PresentMode {
+        if self.vsync {
+            PresentMode::AutoVsync
+        } else {
+            PresentMode::AutoNoVsync
+        }
+    }
+
+    /**
+     * The next window size in the list, or the previous one for a negative step.
+     */
+    pub fn step_window_size(&mut self, step: i32) {
+        let len = WINDOW_SIZES.len() as i32;
+        let next = match WINDOW_SIZES
+            .iter()
+            .position(|size| *size == self.window_size)
+        {
+            Some(i) => (i as i32 + step).rem_euclid(len),
+            None => 0,
+        };
+        self.window_size = WINDOW_SIZES[next as usize];
+    }
+
+    /**
+     * The saved settings, or the defaults when there are none or they can't be read.
+     */
+    pub fn load() -> Settings {
+        let Some(text) = read_settings() else {
+            return Settings::default();
+        };
+        match ron::from_str::<Settings>(&text) {
+            Ok(settings) => settings.sanitized(),
+            Err(err) => {
+                warn!("Ignoring {}: {}", SETTINGS_FILE, err);
+                Settings::default()
+            }
+        }
+    }
+
+    fn sanitized(mut self) -> Settings {
+        let (width, height) = self.window_size;
+        self.window_size = (width.max(64), height.max(64));
+        self.pixel_scale = self.pixel_scale.clamp(1, MAX_PIXEL_SCALE);
+        self.master_volume = self.master_volume.min(100);
+        self.music_volume = self.music_volume.min(100);
+        self.effects_volume = self.effects_volume.min(100);
+        self
+    }
+
+    pub fn save(&self) {
+        match ron::ser::to_string_pretty(self, default()) {
+            Ok(text) => write_settings(&text),
+            Err(err) => error!("Failed to serialize the settings: {}", err),
+        }
+    }
+}
+
+/**
+ * How loud sounds play, kept in line with the settings. Sounds are played with the playback
+ * settings from here, bevy has no volume for everything at once.
+ */
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct Volumes {
+    pub music: f32,
+    pub effects: f32,
+}
+
+impl Volumes {
+    pub fn new(settings: &Settings) -> Volumes {
+        let master = settings.master_volume as f32 / 100.0;
+        Volumes {
+            music: master * settings.music_volume as f32 / 100.0,
+            effects: master * settings.effects_volume as f32 / 100.0,
+        }
+    }
+
+    // nothing plays sounds yet
+    #[allow(dead_code)]
+    pub fn music(&self) -> PlaybackSettings {
+        PlaybackSettings::LOOP.with_volume(self.music)
+    }
+
+    #[allow(dead_code)]
+    pub fn effect(&self) -> PlaybackSettings {
+        PlaybackSettings::ONCE.with_volume(self.effects)
+    }
+}
+
+/**
+ * A file in the game's folder of the user's config directory.
+ */
+#[cfg(not(target_arch = "wasm32"))]
+pub fn config_path(file: &str) -> Option<std::path::PathBuf> {
+    Some(dirs::config_dir()?.join("dungeons-of-redrod").join(file))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn read_config(file: &str) -> Option<String> {
+    std::fs::read_to_string(config_path(file)?).ok()
+}
+
+/**
+ * Writes a file to the game's config folder, creating the folder the first time.
+ */
+#[cfg(not(target_arch = "wasm32"))]
+pub fn write_config(file: &str, text: &str) {
+    let Some(path) = config_path(file) else {
+        warn!("No config directory to save {} in", file);
+        return;
+    };
+    let result = match path.parent() {
+        Some(dir) => std::fs::create_dir_all(dir),
+        None => Ok(()),
+    }
+    .and_then(|()| std::fs::write(&path, text));
+    if let Err(err) = result {
+        error!("Failed to write {}: {}", path.display(), err);
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read_settings() -> Option<String> {
+    read_config(SETTINGS_FILE)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write_settings(text: &str) {
+    write_config(SETTINGS_FILE, text);
+}
+
+#[cfg(target_arch = "wasm32")]
+fn read_settings() -> Option<String> {
+    None
+}
+
+#[cfg(target_arch = "wasm32")]
+fn write_settings(_: &str) {
+    warn!("Settings can't be saved in the browser");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sanitized_keeps_sensible_settings() {
+        assert_eq!(Settings::default().sanitized(), Settings::default());
+    }
+
+    #[test]
+    fn sanitized_clamps() {
+        let settings = Settings {
+            window_size: (0, 10_000),
+            pixel_scale: 0,
+            master_volume: 250,
+            music_volume: 101,
+            effects_volume: 50,
+            ..default()
+        }
+        .sanitized();
+        assert_eq!(settings.window_size, (64, 10_000));
+        assert_eq!(settings.pixel_scale, 1);
+        assert_eq!(settings.master_volume, 100);
+        assert_eq!(settings.music_volume, 100);
+        assert_eq!(settings.effects_volume, 50);
+    }
+}
@@ -0,0 +1,14 @@
+use self::systems::{apply_settings, save_settings};
+use bevy::prelude::*;
+pub mod components;
+mod systems;
+
+/**
+ * Keeps the window and camera in line with the settings, saving them when they change.
+ */
+pub struct SettingsPlugin;
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems((apply_settings, save_settings));
+    }
+}
@@ -0,0 +1,35 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+
+use super::components::{Settings, Volumes};
+
+/**
+ * Resizes the window, rescales the camera and sets the volumes to match the settings.
+ */
+pub fn apply_settings(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+    mut projections: Query<&mut OrthographicProjection>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+
+    for mut window in windows.iter_mut() {
+        let (width, height) = settings.window_size;
+        window.resolution.set(width as f32, height as f32);
+        window.mode = settings.window_mode();
+        window.present_mode = settings.present_mode();
+    }
+    for mut projection in projections.iter_mut() {
+        projection.scale = 1.0 / settings.pixel_scale as f32;
+    }
+    commands.insert_resource(Volumes::new(&settings));
+}
+
+pub fn save_settings(settings: Res<Settings>) {
+    // nothing to save right after loading them
+    if settings.is_changed() && !settings.is_added() {
+        settings.save();
+    }
+}
@@ -0,0 +1,264 @@
+use bevy::prelude::{info, warn};
+use matchbox_protocol::{JsonPeerEvent, JsonPeerRequest, PeerEvent, PeerId, PeerRequest};
+use std::{
+    collections::HashMap,
+    io,
+    net::{IpAddr, TcpListener, TcpStream, UdpSocket},
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+use tungstenite::{
+    handshake::server::{Callback, ErrorResponse, Request, Response},
+    Message, WebSocket,
+};
+use uuid::Uuid;
+
+use crate::config::SignalingHost;
+
+/**
+ * How long a connection waits for a message before passing on the ones meant for its peer.
+ */
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/**
+ * Peers of a room waiting for it to fill up, and who is in it.
+ */
+#[derive(Default)]
+struct Rooms {
+    peers: HashMap<PeerId, Sender<JsonPeerEvent>>,
+    groups: HashMap<u64, Vec<PeerId>>,
+    group_of: HashMap<PeerId, u64>,
+    /// The group still taking peers for each room url.
+    open: HashMap<String, u64>,
+    next_group: u64,
+}
+
+impl Rooms {
+    /**
+     * Adds the peer to the open group of the room, introducing it to everyone already in there.
+     */
+    fn join(
+        &mut self,
+        room: String,
+        size: Option<usize>,
+        id: PeerId,
+        sender: Sender<JsonPeerEvent>,
+    ) {
+        let group = match self.open.get(&room) {
+            Some(group) => *group,
+            None => {
+                self.next_group += 1;
+                self.open.insert(room.clone(), self.next_group);
+                self.next_group
+            }
+        };
+        let members = self.groups.entry(group).or_default();
+        for member in members.iter() {
+            if let Some(peer) = self.peers.get(member) {
+                let _ = peer.send(PeerEvent::NewPeer(id));
+            }
+        }
+        members.push(id);
+        if size.is_some_and(|size| members.len() >= size) {
+            // latecomers start a group of their own
+            self.open.remove(&room);
+        }
+        self.peers.insert(id, sender);
+        self.group_of.insert(id, group);
+    }
+
+    /**
+     * Passes the signal on, as long as both peers were matched into the same group.
+     */
+    fn signal(&self, sender: PeerId, receiver: PeerId, data: serde_json::Value) {
+        let group = self.group_of.get(&sender);
+        let same_group = group.is_some() && group == self.group_of.get(&receiver);
+        if !same_group {
+            warn!(
+                "Dropping a signal from {:?} for {:?} outside of its group",
+                sender, receiver
+            );
+            return;
+        }
+        match self.peers.get(&receiver) {
+            Some(peer) => {
+                let _ = peer.send(PeerEvent::Signal { sender, data });
+            }
+            None => warn!("Signal from {:?} for unknown peer {:?}", sender, receiver),
+        }
+    }
+
+    fn leave(&mut self, id: PeerId) {
+        self.peers.remove(&id);
+        let Some(group) = self.group_of.remove(&id) else {
+            return;
+        };
+        let Some(members) = self.groups.get_mut(&group) else {
+            return;
+        };
+        members.retain(|member| *member != id);
+        for member in members.iter() {
+            if let Some(peer) = self.peers.get(member) {
+                let _ = peer.send(PeerEvent::PeerLeft(id));
+            }
+        }
+        if members.is_empty() {
+            self.groups.remove(&group);
+            self.open.retain(|_, open| *open != group);
+        }
+    }
+}
+
+/**
+ * The address of the interface traffic leaves through. Connecting a UDP socket sends nothing, it
+ * only picks the route.
+ */
+fn lan_address() -> Option<IpAddr> {
+    let socket = UdpSocket::bind(("0.0.0.0", 0)).ok()?;
+    socket.connect(("8.8.8.8", 80)).ok()?;
+    let address = socket.local_addr().ok()?.ip();
+    (!address.is_loopback() && !address.is_unspecified()).then_some(address)
+}
+
+/**
+ * Starts a matchbox compatible signaling server on the port, running until the game exits.
+ */
+pub fn start_signaling_server(port: u16) -> io::Result<SignalingHost> {
+    let listener = TcpListener::bind(("0.0.0.0", port))?;
+    info!("Signaling server listening on port {}", port);
+
+    let rooms = Arc::new(Mutex::new(Rooms::default()));
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let rooms = rooms.clone();
+                    thread::spawn(move || serve_peer(stream, rooms));
+                }
+                Err(err) => warn!("Failed to accept a peer: {}", err),
+            }
+        }
+    });
+    Ok(SignalingHost {
+        port,
+        address: lan_address(),
+    })
+}
+
+/**
+ * The room url without the query, and how many peers it takes to fill it up.
+ */
+fn parse_room(uri: &str) -> (String, Option<usize>) {
+    let (room, query) = uri.split_once('?').unwrap_or((uri, ""));
+    let size = query
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("next="))
+        .and_then(|next| next.parse().ok());
+    (room.to_string(), size)
+}
+
+fn new_peer_id() -> PeerId {
+    let mut bytes = [0u8; 16];
+    getrandom::getrandom(&mut bytes).expect("no source of randomness");
+    PeerId(Uuid::from_bytes(bytes))
+}
+
+/**
+ * Remembers the url the peer connected to, it names the room they want to join.
+ */
+struct RoomUri<'a>(&'a mut String);
+
+impl Callback for RoomUri<'_> {
+    fn on_request(self, request: &Request, response: Response) -> Result<Response, ErrorResponse> {
+        *self.0 = request.uri().to_string();
+        Ok(response)
+    }
+}
+
+fn serve_peer(stream: TcpStream, rooms: Arc<Mutex<Rooms>>) {
+    let mut uri = String::new();
+    let websocket = tungstenite::accept_hdr(stream, RoomUri(&mut uri));
+    let mut websocket = match websocket {
+        Ok(websocket) => websocket,
+        Err(err) => {
+            warn!("Failed the websocket handshake: {}", err);
+            return;
+        }
+    };
+    if let Err(err) = websocket.get_ref().set_read_timeout(Some(POLL_INTERVAL)) {
+        warn!("Failed to configure a peer connection: {}", err);
+        return;
+    }
+
+    let id = new_peer_id();
+    let (room, size) = parse_room(&uri);
+    let (sender, receiver) = mpsc::channel();
+    let _ = sender.send(PeerEvent::IdAssigned(id));
+    rooms.lock().unwrap().join(room, size, id, sender);
+    info!("{:?} joined {}", id, uri);
+
+    relay(&mut websocket, id, &receiver, &rooms);
+
+    rooms.lock().unwrap().leave(id);
+    info!("{:?} left", id);
+}
+
+/**
+ * Passes signals between the peer and the others until it disconnects.
+ */
+fn relay(
+    websocket: &mut WebSocket<TcpStream>,
+    id: PeerId,
+    receiver: &Receiver<JsonPeerEvent>,
+    rooms: &Mutex<Rooms>,
+) {
+    loop {
+        for event in receiver.try_iter() {
+            if websocket
+                .write_message(Message::Text(event.to_string()))
+                .is_err()
+            {
+                return;
+            }
+        }
+
+        match websocket.read_message() {
+            Ok(Message::Text(text)) => match text.parse::<JsonPeerRequest>() {
+                Ok(PeerRequest::Signal { receiver, data }) => {
+                    rooms.lock().unwrap().signal(id, receiver, data);
+                }
+                Ok(PeerRequest::KeepAlive) => {}
+                Err(err) => warn!("Invalid request from {:?}: {}", id, err),
+            },
+            Ok(Message::Close(_)) => return,
+            Ok(_) => {}
+            Err(tungstenite::Error::Io(err))
+                if matches!(
+                    err.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) => {}
+            Err(_) => return,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn room_and_size() {
+        assert_eq!(parse_room("/room?next=3"), ("/room".into(), Some(3)));
+        assert_eq!(parse_room("/room?a=b&next=2"), ("/room".into(), Some(2)));
+    }
+
+    #[test]
+    fn room_without_size() {
+        assert_eq!(parse_room("/room"), ("/room".into(), None));
+        assert_eq!(parse_room("/room?next=many"), ("/room".into(), None));
+    }
+}